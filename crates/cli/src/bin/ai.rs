@@ -1,61 +1,130 @@
-use cli::{get_json, post_json, JoinResponse, MoveRequest, StateResponse};
-use game_ai_entropy::UseEntropyPlayer;
-use game_ai_random::RandomPlayer;
-use game_ai_search::SearchPlayer;
-use game_ai_unfair::Unfair;
-use game_core::{abstract_game::Agent, defs::Game};
-use std::{thread, time::Duration};
+use cli::{Client, GameId};
+use game_core::commitment::verify_reveal;
+use game_core::runner::{Runner, RunnerConfig};
+use game_registry::{AgentSpec, BuildContext};
+use std::time::Duration;
+
+const USAGE: &str = "ai <strategy> <port> <game> [--seed N]";
+// 手番が来るまで /events で待つ。時間切れでも state を聞き直すだけ
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(30);
 
 fn main() {
     let strategy = std::env::args()
         .nth(1)
-        .unwrap_or_else(|| usage_and_exit("ai <strategy> <port>"));
+        .unwrap_or_else(|| usage_and_exit(USAGE));
     let port = std::env::args()
         .nth(2)
-        .unwrap_or_else(|| usage_and_exit("ai <strategy> <port>"))
+        .unwrap_or_else(|| usage_and_exit(USAGE))
         .parse::<u16>()
-        .unwrap_or_else(|_| usage_and_exit("ai <strategy> <port>"));
+        .unwrap_or_else(|_| usage_and_exit(USAGE));
+    let game = std::env::args()
+        .nth(3)
+        .unwrap_or_else(|| usage_and_exit(USAGE))
+        .parse::<GameId>()
+        .unwrap_or_else(|_| usage_and_exit(USAGE));
+    // AI の乱数の seed。省略するとゲームの番号を使うので、同じ spec なら同じように打つ
+    let seed = match std::env::args().nth(4).as_deref() {
+        None => game as u64,
+        Some("--seed") => std::env::args()
+            .nth(5)
+            .and_then(|seed| seed.parse::<u64>().ok())
+            .unwrap_or_else(|| usage_and_exit(USAGE)),
+        Some(_) => usage_and_exit(USAGE),
+    };
+    let client = Client::new(port);
+
+    // 席を取る前に作って、spec が間違っていれば席を取らずに終わる
+    let mut agent = AgentSpec::parse(&strategy)
+        .and_then(|spec| {
+            spec.build(&BuildContext {
+                seed: Some(seed),
+                ..Default::default()
+            })
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            usage_and_exit(&format!("{USAGE}\n{}", game_registry::help()))
+        });
 
-    let joined: JoinResponse =
-        post_json(port, "/join", &serde_json::json!({}), None).expect("failed to join game server");
+    let joined = client.join(game).expect("failed to join game server");
     println!(
-        "joined as player {} with strategy {}",
+        "joined game {game} as player {} with strategy {}",
         joined.player, strategy
     );
+    if joined.creator_seeded {
+        eprintln!("warning: the creator of game {game} chose the seed and knows the deal");
+    }
+    if joined.has_referee {
+        eprintln!("warning: game {game} has a referee who can see the deal");
+    }
     let secret = joined.secret;
 
-    let mut agent = build_agent(&strategy);
+    // 履歴を何手目まで agent に渡したか
+    let mut observed: Option<usize> = None;
+    // agent が panic したり合法でない手を返したら、ランダムに打って続ける
+    let mut runner = Runner::new(RunnerConfig {
+        seed,
+        ..Default::default()
+    });
 
     loop {
-        let state: StateResponse =
-            get_json(port, "/state", Some(&secret)).expect("failed to fetch state");
+        let state = client.state(game, &secret).expect("failed to fetch state");
+
+        let history = &state.info.query_answer;
+        let seen = observed.unwrap_or_else(|| {
+            agent.on_game_start(&state.info.config, joined.player);
+            0
+        });
+        for move_ans in &history[seen..] {
+            agent.observe(move_ans);
+        }
+        observed = Some(history.len());
 
         if let Some(winner) = state.winner.clone() {
+            agent.on_game_end(&winner);
             println!("game finished: {winner:?}");
+            // join のときに受け取った commitment で配り方を確かめる
+            let verified = client
+                .reveal(game)
+                .map_err(|err| err.to_string())
+                .and_then(|reveal| {
+                    verify_reveal(&joined.commitment, &reveal)?;
+                    state.check_reveal(&reveal)
+                });
+            match verified {
+                Ok(()) => println!(
+                    "deal verified against commitment {}",
+                    joined.commitment.hash
+                ),
+                Err(err) => eprintln!("deal verification failed: {err}"),
+            }
             break;
         }
 
         if !state.your_turn {
-            thread::sleep(Duration::from_millis(1000));
+            client
+                .events(game, Some(&secret), history.len(), EVENTS_TIMEOUT)
+                .expect("failed to wait for events");
             continue;
         }
 
-        let info = state.info;
-        let possible_moves = state.possible_moves;
-        let action = agent.use_info(info, possible_moves);
-        let _response: serde_json::Value =
-            post_json(port, "/move", &MoveRequest { action }, Some(&secret))
-                .expect("failed to submit move");
-    }
-}
-
-fn build_agent(strategy: &str) -> Box<dyn Agent<Game = Game>> {
-    match strategy {
-        "random" => Box::new(RandomPlayer::default()),
-        "entropy" => Box::new(UseEntropyPlayer),
-        "search" => Box::new(SearchPlayer::new(2)),
-        "unfair" => Box::new(Unfair::new(0.7)),
-        _ => usage_and_exit("ai <random|entropy|search|unfair> <port>"),
+        let turn = history.len();
+        let faults = runner.faults().len();
+        let Some(action) = runner.choose(
+            &mut agent,
+            joined.player,
+            turn,
+            state.info,
+            state.possible_moves,
+        ) else {
+            continue;
+        };
+        for fault in &runner.faults()[faults..] {
+            eprintln!("agent fault at turn {turn}: {:?}", fault.fault);
+        }
+        client
+            .submit_move(game, &secret, action)
+            .expect("failed to submit move");
     }
 }
 
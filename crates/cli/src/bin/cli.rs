@@ -1,6 +1,9 @@
-use cli::{get_json, post_json, JoinResponse, MoveRequest, StateResponse};
-use game_core::defs::Move;
-use std::{env, fs};
+use cli::{format_move_ans, Client, CreateGameRequest, GameEvent, GameId};
+use game_core::{
+    commitment::{verify_reveal, Commitment},
+    defs::{GameConfig, Move},
+};
+use std::{env, fs, time::Duration};
 
 fn main() {
     let mut args = env::args().skip(1).collect::<Vec<_>>();
@@ -10,21 +13,112 @@ fn main() {
 
     let command = args.remove(0);
     match command.as_str() {
+        "create" => command_create(args),
+        "games" => command_games(args),
         "join" => command_join(args),
         "state" => command_state(args),
         "move" => command_move(args),
+        "events" => command_events(args),
+        "watch" => command_watch(args),
+        "reveal" => command_reveal(args),
+        "verify" => command_verify(args),
         _ => usage_and_exit(),
     }
 }
 
+fn command_create(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let json = parser.take_flag("--json");
+    let referee = parser.take_flag("--referee");
+    let config = parser.take_option("--config");
+    let config_json = parser.take_option("--config-file").map(|path| {
+        let text = fs::read_to_string(path).expect("failed to read config file");
+        serde_json::from_str::<GameConfig>(&text).expect("invalid config file")
+    });
+    let player_num = parser
+        .take_option("--players")
+        .map(|value| value.parse::<usize>().unwrap_or_else(|_| usage_and_exit()));
+    let seed = parser
+        .take_option("--seed")
+        .map(|value| value.parse::<u64>().unwrap_or_else(|_| usage_and_exit()));
+    parser.finish();
+
+    let request = CreateGameRequest {
+        config,
+        config_json,
+        player_num,
+        seed,
+        referee,
+    };
+    let created = Client::new(port)
+        .create_game(&request)
+        .expect("failed to create game");
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&created).expect("failed to serialize create response")
+        );
+    } else {
+        println!("game: {}", created.game_id);
+        println!("player_num: {}", created.player_num);
+        println!("commitment: {}", created.commitment.hash);
+        println!("spectator_token: {}", created.spectator_token);
+        if let Some(token) = &created.referee_token {
+            println!("referee_token: {token}");
+        }
+    }
+}
+
+fn command_games(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let json = parser.take_flag("--json");
+    parser.finish();
+
+    let games = Client::new(port)
+        .open_games()
+        .expect("failed to list games");
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&games).expect("failed to serialize games")
+        );
+    } else {
+        for game in &games {
+            println!(
+                "game {}: {} {}/{} joined{}{}",
+                game.game_id,
+                game.config,
+                game.joined,
+                game.player_num,
+                if game.creator_seeded {
+                    " (seeded by creator)"
+                } else {
+                    ""
+                },
+                if game.has_referee {
+                    " (has referee)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+}
+
 fn command_join(args: Vec<String>) {
     let mut parser = ArgParser::new(args);
     let port = parser.port();
     let json = parser.take_flag("--json");
+    let game = parser.game();
     parser.finish();
 
-    let joined: JoinResponse =
-        post_json(port, "/join", &serde_json::json!({}), None).expect("failed to join game server");
+    let joined = Client::new(port)
+        .join(game)
+        .expect("failed to join game server");
 
     if json {
         println!(
@@ -35,6 +129,13 @@ fn command_join(args: Vec<String>) {
         println!("player: {}", joined.player);
         println!("player_num: {}", joined.player_num);
         println!("secret: {}", joined.secret);
+        println!("commitment: {}", joined.commitment.hash);
+        if joined.creator_seeded {
+            println!("warning: the creator chose the seed and knows the deal");
+        }
+        if joined.has_referee {
+            println!("warning: this game has a referee who can see the deal");
+        }
     }
 }
 
@@ -43,10 +144,12 @@ fn command_state(args: Vec<String>) {
     let port = parser.port();
     let json = parser.take_flag("--json");
     let secret = parser.resolve_secret();
+    let game = parser.game();
     parser.finish();
 
-    let state: StateResponse =
-        get_json(port, "/state", Some(&secret)).expect("failed to fetch state");
+    let state = Client::new(port)
+        .state(game, &secret)
+        .expect("failed to fetch state");
 
     if json {
         println!(
@@ -72,12 +175,13 @@ fn command_move(args: Vec<String>) {
     let port = parser.port();
     let json = parser.take_flag("--json");
     let secret = parser.resolve_secret();
+    let game = parser.game();
     let action = parse_move(&mut parser);
     parser.finish();
 
-    let response: serde_json::Value =
-        post_json(port, "/move", &MoveRequest { action }, Some(&secret))
-            .expect("failed to submit move");
+    let response = Client::new(port)
+        .submit_move(game, &secret, action)
+        .expect("failed to submit move");
 
     if json {
         println!(
@@ -85,7 +189,150 @@ fn command_move(args: Vec<String>) {
             serde_json::to_string(&response).expect("failed to serialize move response")
         );
     } else {
-        println!("{response}");
+        println!("accepted: {}", response.accepted);
+        println!("winner: {:?}", response.winner);
+    }
+}
+
+// since 番目からの出来事を、何か起きるまで待って表示する
+fn command_events(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let json = parser.take_flag("--json");
+    let since = parser
+        .take_option("--since")
+        .map(|value| value.parse::<usize>().unwrap_or_else(|_| usage_and_exit()))
+        .unwrap_or(0);
+    let timeout = parser
+        .take_option("--timeout-ms")
+        .map(|value| value.parse::<u64>().unwrap_or_else(|_| usage_and_exit()))
+        .unwrap_or(30_000);
+    let secret = parser.take_secret();
+    let game = parser.game();
+    parser.finish();
+
+    let response = Client::new(port)
+        .events(
+            game,
+            secret.as_deref(),
+            since,
+            Duration::from_millis(timeout),
+        )
+        .expect("failed to fetch events");
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&response).expect("failed to serialize events")
+        );
+    } else {
+        for event in &response.events {
+            match event {
+                GameEvent::Move {
+                    index,
+                    move_ans,
+                    current_turn,
+                } => println!(
+                    "{index}: {} (next: player {current_turn})",
+                    format_move_ans(move_ans)
+                ),
+                GameEvent::GameEnd { index, winner } => {
+                    println!("{index}: game finished: {winner:?}")
+                }
+            }
+        }
+        println!("next: {}", response.next);
+    }
+}
+
+// 席を取らずに見る。--secret には観戦用か referee の token も渡せる
+fn command_watch(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let json = parser.take_flag("--json");
+    let secret = parser.take_secret();
+    let game = parser.game();
+    parser.finish();
+
+    let watch = Client::new(port)
+        .watch(game, secret.as_deref())
+        .expect("failed to watch game");
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&watch).expect("failed to serialize watch response")
+        );
+    } else {
+        println!("role: {:?}", watch.role);
+        println!("current_turn: {}", watch.current_turn);
+        println!("winner: {:?}", watch.winner);
+        if let Some(distr) = &watch.distr {
+            println!("distr: {distr:?}");
+        }
+        println!("history:");
+        for line in &watch.history {
+            println!("  {line}");
+        }
+    }
+}
+
+fn command_reveal(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let json = parser.take_flag("--json");
+    let game = parser.game();
+    parser.finish();
+
+    let reveal = Client::new(port)
+        .reveal(game)
+        .expect("failed to fetch reveal");
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&reveal).expect("failed to serialize reveal")
+        );
+    } else {
+        println!("deal_version: {}", reveal.deal_version);
+        println!("seed: {}", reveal.seed);
+        println!("salt: {}", reveal.salt);
+        println!("distr: {:?}", reveal.distr);
+        println!("history: {:?}", reveal.history);
+    }
+}
+
+// join のときに控えた commitment と、決着後の reveal を突き合わせる。
+// secret を渡せば、自分に見えていた手札と履歴とも突き合わせる
+fn command_verify(args: Vec<String>) {
+    let mut parser = ArgParser::new(args);
+    let port = parser.port();
+    let hash = parser
+        .take_option("--commitment")
+        .unwrap_or_else(|| usage_and_exit());
+    let secret = parser.take_secret();
+    let game = parser.game();
+    parser.finish();
+
+    let client = Client::new(port);
+    let reveal = client.reveal(game).expect("failed to fetch reveal");
+    // 版もハッシュに入っているので、版は reveal のものを使ってよい
+    let commitment = Commitment {
+        deal_version: reveal.deal_version,
+        hash,
+    };
+    let mut result = verify_reveal(&commitment, &reveal);
+    if let (Ok(()), Some(secret)) = (&result, secret) {
+        let state = client.state(game, &secret).expect("failed to fetch state");
+        result = state.check_reveal(&reveal);
+    }
+
+    match result {
+        Ok(()) => println!("ok: seed {} matches the commitment", reveal.seed),
+        Err(err) => {
+            eprintln!("verification failed: {err}");
+            std::process::exit(1);
+        }
     }
 }
 
@@ -135,6 +382,13 @@ impl ArgParser {
             .unwrap_or_else(|_| usage_and_exit())
     }
 
+    fn game(&mut self) -> GameId {
+        self.take_positional()
+            .unwrap_or_else(|| usage_and_exit())
+            .parse::<GameId>()
+            .unwrap_or_else(|_| usage_and_exit())
+    }
+
     fn take_flag(&mut self, flag: &str) -> bool {
         if let Some(index) = self.args.iter().position(|arg| arg == flag) {
             self.args.remove(index);
@@ -167,6 +421,13 @@ impl ArgParser {
     }
 
     fn resolve_secret(&mut self) -> String {
+        self.take_secret().unwrap_or_else(|| {
+            eprintln!("missing secret: use --secret, --secret-file, or GAME_SECRET");
+            std::process::exit(2);
+        })
+    }
+
+    fn take_secret(&mut self) -> Option<String> {
         let secret = self.take_option("--secret");
         let secret_file = self.take_option("--secret-file");
 
@@ -176,22 +437,19 @@ impl ArgParser {
         }
 
         if let Some(secret) = secret {
-            return secret;
+            return Some(secret);
         }
         if let Some(path) = secret_file {
-            return fs::read_to_string(path)
-                .expect("failed to read secret file")
-                .trim()
-                .to_string();
-        }
-        if let Ok(secret) = env::var("GAME_SECRET") {
-            if !secret.trim().is_empty() {
-                return secret;
-            }
+            return Some(
+                fs::read_to_string(path)
+                    .expect("failed to read secret file")
+                    .trim()
+                    .to_string(),
+            );
         }
-
-        eprintln!("missing secret: use --secret, --secret-file, or GAME_SECRET");
-        std::process::exit(2);
+        env::var("GAME_SECRET")
+            .ok()
+            .filter(|secret| !secret.trim().is_empty())
     }
 
     fn finish(self) {
@@ -203,11 +461,19 @@ impl ArgParser {
 
 fn usage_and_exit() -> ! {
     eprintln!("usage:");
-    eprintln!("  cli join <port> [--json]");
-    eprintln!("  cli state <port> [--secret <secret> | --secret-file <path>] [--json]");
-    eprintln!("  cli move <port> [--secret <secret> | --secret-file <path>] [--json] query <player> <sort>");
+    eprintln!("  cli create <port> [--config <name> | --config-file <path>] [--players <n>] [--seed <n>] [--referee] [--json]");
+    eprintln!("  cli games <port> [--json]");
+    eprintln!("  cli join <port> <game> [--json]");
+    eprintln!("  cli state <port> <game> [--secret <secret> | --secret-file <path>] [--json]");
+    eprintln!("  cli move <port> <game> [--secret <secret> | --secret-file <path>] [--json] query <player> <sort>");
+    eprintln!(
+        "  cli move <port> <game> [--secret <secret> | --secret-file <path>] [--json] declare <card>..."
+    );
+    eprintln!("  cli events <port> <game> [--secret <secret or token>] [--since <n>] [--timeout-ms <n>] [--json]");
+    eprintln!("  cli watch <port> <game> [--secret <secret or token>] [--json]");
+    eprintln!("  cli reveal <port> <game> [--json]");
     eprintln!(
-        "  cli move <port> [--secret <secret> | --secret-file <path>] [--json] declare <card>..."
+        "  cli verify <port> <game> --commitment <hash> [--secret <secret> | --secret-file <path>]"
     );
     std::process::exit(2);
 }
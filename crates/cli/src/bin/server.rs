@@ -1,29 +1,76 @@
-use cli::{host::GameHost, ErrorResponse, MoveRequest};
-use game_core::config::three_midium;
+use cli::{
+    lobby::{spawn_sweeper, Lobby, LobbyOptions},
+    ws::{self, Message, MessageReader},
+    ClientMessage, CreateGameRequest, ErrorResponse, GameEvent, GameId, MoveRequest, ServerMessage,
+};
 use game_core::defs::Move;
 use std::{
-    io::{BufRead, BufReader, Read, Write},
+    io::{self, BufRead, BufReader, Read, Write},
     net::{TcpListener, TcpStream},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+const USAGE: &str = "server <port> [--max-games N] [--keep-finished-secs N] [--idle-secs N] [--allow-origin ORIGIN]...";
+
+// /events で待つ時間の既定と上限
+const EVENTS_TIMEOUT_MS: u64 = 30_000;
+const MAX_EVENTS_TIMEOUT_MS: u64 = 120_000;
+
+// 手が進むたびに moved で /events を待っている接続を起こす
+struct Shared {
+    lobby: Arc<Mutex<Lobby>>,
+    moved: Condvar,
+    // /ws の handshake で、server 自身のほかに受ける Origin（"*" ならどこでも）
+    allowed_origins: Vec<String>,
+}
+
 fn main() {
-    let port = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| usage_and_exit("server <port>"))
+    let mut args = std::env::args().skip(1);
+    let port = args
+        .next()
+        .unwrap_or_else(|| usage_and_exit(USAGE))
         .parse::<u16>()
-        .unwrap_or_else(|_| usage_and_exit("server <port>"));
+        .unwrap_or_else(|_| usage_and_exit(USAGE));
+    let mut options = LobbyOptions::default();
+    let mut allowed_origins = vec![];
+    while let Some(flag) = args.next() {
+        if flag == "--allow-origin" {
+            allowed_origins.push(args.next().unwrap_or_else(|| usage_and_exit(USAGE)));
+            continue;
+        }
+        let value = args
+            .next()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or_else(|| usage_and_exit(USAGE));
+        match flag.as_str() {
+            "--max-games" => options.max_games = value as usize,
+            "--keep-finished-secs" => options.keep_finished = Duration::from_secs(value),
+            "--idle-secs" => options.idle_timeout = Duration::from_secs(value),
+            _ => usage_and_exit(USAGE),
+        }
+    }
 
-    let host = Arc::new(Mutex::new(GameHost::new(three_midium())));
+    let interval = options.sweep_interval();
+    let shared = Arc::new(Shared {
+        lobby: Arc::new(Mutex::new(Lobby::new(options))),
+        moved: Condvar::new(),
+        allowed_origins,
+    });
+    // create や一覧が来なくても、決着したゲームや放置されたゲームを消す
+    spawn_sweeper(&shared.lobby, interval);
     let listener = TcpListener::bind(("127.0.0.1", port)).expect("failed to bind port");
     println!("listening on http://127.0.0.1:{port}");
 
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                let host = Arc::clone(&host);
+                let shared = Arc::clone(&shared);
                 std::thread::spawn(move || {
-                    if let Err(err) = handle_connection(stream, host) {
+                    if let Err(err) = handle_connection(stream, &shared) {
                         eprintln!("{err}");
                     }
                 });
@@ -33,7 +80,7 @@ fn main() {
     }
 }
 
-fn handle_connection(stream: TcpStream, host: Arc<Mutex<GameHost>>) -> Result<(), String> {
+fn handle_connection(stream: TcpStream, shared: &Arc<Shared>) -> Result<(), String> {
     let mut reader = BufReader::new(stream);
     let mut request_line = String::new();
     reader
@@ -45,6 +92,9 @@ fn handle_connection(stream: TcpStream, host: Arc<Mutex<GameHost>>) -> Result<()
 
     let mut content_length = 0usize;
     let mut player_secret: Option<String> = None;
+    let mut websocket_key: Option<String> = None;
+    let mut host: Option<String> = None;
+    let mut origin: Option<String> = None;
     loop {
         let mut line = String::new();
         reader.read_line(&mut line).map_err(|err| err.to_string())?;
@@ -58,7 +108,33 @@ fn handle_connection(stream: TcpStream, host: Arc<Mutex<GameHost>>) -> Result<()
             if name.eq_ignore_ascii_case("x-player-secret") {
                 player_secret = Some(value.trim().to_string());
             }
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.trim().to_string());
+            }
+            if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            }
+            if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    // GET /ws だけは WebSocket に切り替えて、閉じられるまで読み続ける
+    if let Some(key) =
+        websocket_key.filter(|_| request_line.split_whitespace().nth(1) == Some("/ws"))
+    {
+        // ほかのサイトのページから勝手につながれないように、ブラウザからは Origin を確かめる
+        if !ws::origin_allowed(origin.as_deref(), host.as_deref(), &shared.allowed_origins) {
+            let mut stream = reader.into_inner();
+            let peer = peer_addr(&stream);
+            eprintln!(
+                "[ws/reject] peer={peer} origin={} reason=origin",
+                origin.unwrap_or_default()
+            );
+            return write_error(&mut stream, 403, "origin not allowed");
         }
+        return serve_websocket(reader, &key, shared);
     }
 
     let mut body = vec![0; content_length];
@@ -70,70 +146,169 @@ fn handle_connection(stream: TcpStream, host: Arc<Mutex<GameHost>>) -> Result<()
     let mut parts = request_line.split_whitespace();
     let method = parts.next().ok_or("missing method")?;
     let path = parts.next().ok_or("missing path")?;
-    let peer = stream
-        .peer_addr()
-        .map(|addr| addr.to_string())
-        .unwrap_or_else(|_| "<unknown>".to_string());
-
-    match (method, path) {
-        ("POST", "/join") => {
-            let mut host = host.lock().map_err(|_| "lock poisoned".to_string())?;
-            let Some(response) = host.join() else {
-                eprintln!("[join/reject] peer={peer} reason=all_players_joined");
-                return write_error(&mut stream, 409, "all players are already joined");
-            };
-            eprintln!(
-                "[join] peer={peer} player={} secret={}",
-                response.player,
-                short_secret(&response.secret)
-            );
-            write_json(&mut stream, 200, &response)
-        }
-        ("POST", "/move") => {
-            let request: MoveRequest =
+    let peer = peer_addr(&stream);
+
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    let route: Vec<&str> = route.trim_start_matches('/').split('/').collect();
+    match (method, route.as_slice()) {
+        ("POST", ["games"]) => {
+            let request: CreateGameRequest =
                 serde_json::from_slice(&body).map_err(|err| err.to_string())?;
-            let Some(secret) = player_secret.as_deref() else {
-                eprintln!("[move/reject] peer={peer} reason=missing_secret");
-                return write_error(&mut stream, 401, "missing player secret");
-            };
-            let mut host = host.lock().map_err(|_| "lock poisoned".to_string())?;
-            let action = request.action;
-            match host.apply_move(secret, action.clone()) {
+            let mut lobby = shared
+                .lobby
+                .lock()
+                .map_err(|_| "lock poisoned".to_string())?;
+            match lobby.create(request) {
                 Ok(response) => {
                     eprintln!(
-                        "[move] peer={peer} secret={} action={}",
-                        short_secret(secret),
-                        format_move(&action)
+                        "[create] peer={peer} game={} player_num={}",
+                        response.game_id, response.player_num
                     );
-                    if let Some(winner) = &response.winner {
-                        eprintln!("[win] winner={winner:?}");
-                    }
                     write_json(&mut stream, 200, &response)
                 }
                 Err(err) => {
-                    eprintln!(
-                        "[move/reject] peer={peer} secret={} action={} reason={err}",
-                        short_secret(secret),
-                        format_move(&action)
-                    );
-                    write_error(&mut stream, 401, &err)
+                    eprintln!("[create/reject] peer={peer} reason={err}");
+                    write_error(&mut stream, 400, &err)
+                }
+            }
+        }
+        ("GET", ["games"]) => {
+            let mut lobby = shared
+                .lobby
+                .lock()
+                .map_err(|_| "lock poisoned".to_string())?;
+            write_json(&mut stream, 200, &lobby.open_games())
+        }
+        ("GET", ["games", game_id, "events"]) => {
+            let Ok(game_id) = game_id.parse::<GameId>() else {
+                return write_error(&mut stream, 404, "not found");
+            };
+            let since = query_param(query, "since").unwrap_or(0) as usize;
+            let timeout = query_param(query, "timeout_ms")
+                .unwrap_or(EVENTS_TIMEOUT_MS)
+                .min(MAX_EVENTS_TIMEOUT_MS);
+            let deadline = Instant::now() + Duration::from_millis(timeout);
+            let mut lobby = shared
+                .lobby
+                .lock()
+                .map_err(|_| "lock poisoned".to_string())?;
+            // 新しい出来事があるか、時間切れになるまで待つ
+            loop {
+                let Some(host) = lobby.host(game_id) else {
+                    eprintln!("[events/reject] peer={peer} game={game_id} reason=unknown_game");
+                    return write_error(&mut stream, 404, "unknown game");
+                };
+                if host.watch_role(player_secret.as_deref()).is_none() {
+                    eprintln!("[events/reject] peer={peer} game={game_id} reason=invalid_secret");
+                    return write_error(&mut stream, 401, "missing or invalid secret");
+                }
+                let response = host.events_since(since);
+                let now = Instant::now();
+                if !response.events.is_empty() || now >= deadline {
+                    drop(lobby);
+                    return write_json(&mut stream, 200, &response);
                 }
+                lobby = shared
+                    .moved
+                    .wait_timeout(lobby, deadline - now)
+                    .map_err(|_| "lock poisoned".to_string())?
+                    .0;
             }
         }
-        ("GET", "/state") => {
-            let Some(secret) = player_secret.as_deref() else {
-                eprintln!("[state/reject] peer={peer} reason=missing_secret");
-                return write_error(&mut stream, 401, "missing player secret");
+        (_, ["games", game_id, action]) => {
+            let Ok(game_id) = game_id.parse::<GameId>() else {
+                return write_error(&mut stream, 404, "not found");
             };
-            let host = host.lock().map_err(|_| "lock poisoned".to_string())?;
-            match host.state_for_secret(secret) {
-                Some(response) => write_json(&mut stream, 200, &response),
-                None => {
+            let mut lobby = shared
+                .lobby
+                .lock()
+                .map_err(|_| "lock poisoned".to_string())?;
+            let Some(host) = lobby.host_mut(game_id) else {
+                eprintln!("[request/reject] peer={peer} game={game_id} reason=unknown_game");
+                return write_error(&mut stream, 404, "unknown game");
+            };
+            match (method, *action) {
+                ("POST", "join") => {
+                    let Some(response) = host.join() else {
+                        eprintln!(
+                            "[join/reject] peer={peer} game={game_id} reason=all_players_joined"
+                        );
+                        return write_error(&mut stream, 409, "all players are already joined");
+                    };
+                    eprintln!(
+                        "[join] peer={peer} game={game_id} player={} secret={}",
+                        response.player,
+                        short_secret(&response.secret)
+                    );
+                    write_json(&mut stream, 200, &response)
+                }
+                ("POST", "move") => {
+                    let request: MoveRequest =
+                        serde_json::from_slice(&body).map_err(|err| err.to_string())?;
+                    let Some(secret) = player_secret.as_deref() else {
+                        eprintln!("[move/reject] peer={peer} game={game_id} reason=missing_secret");
+                        return write_error(&mut stream, 401, "missing player secret");
+                    };
+                    let action = request.action;
+                    match host.apply_move(secret, action.clone()) {
+                        Ok(response) => {
+                            eprintln!(
+                                "[move] peer={peer} game={game_id} secret={} action={}",
+                                short_secret(secret),
+                                format_move(&action)
+                            );
+                            if let Some(winner) = &response.winner {
+                                eprintln!("[win] game={game_id} winner={winner:?}");
+                            }
+                            shared.moved.notify_all();
+                            write_json(&mut stream, 200, &response)
+                        }
+                        Err(err) => {
+                            eprintln!(
+                                "[move/reject] peer={peer} game={game_id} secret={} action={} reason={err}",
+                                short_secret(secret),
+                                format_move(&action)
+                            );
+                            write_error(&mut stream, 401, &err)
+                        }
+                    }
+                }
+                ("GET", "watch") => match host.watch(player_secret.as_deref()) {
+                    Some(response) => write_json(&mut stream, 200, &response),
+                    None => {
+                        eprintln!(
+                            "[watch/reject] peer={peer} game={game_id} reason=invalid_secret"
+                        );
+                        write_error(&mut stream, 401, "missing or invalid secret")
+                    }
+                },
+                ("GET", "reveal") => match host.reveal() {
+                    Some(reveal) => write_json(&mut stream, 200, &reveal),
+                    None => write_error(&mut stream, 409, "game is not over"),
+                },
+                ("GET", "state") => {
+                    let Some(secret) = player_secret.as_deref() else {
+                        eprintln!(
+                            "[state/reject] peer={peer} game={game_id} reason=missing_secret"
+                        );
+                        return write_error(&mut stream, 401, "missing player secret");
+                    };
+                    match host.state_for_secret(secret) {
+                        Some(response) => write_json(&mut stream, 200, &response),
+                        None => {
+                            eprintln!(
+                                "[state/reject] peer={peer} game={game_id} secret={} reason=invalid_secret",
+                                short_secret(secret)
+                            );
+                            write_error(&mut stream, 401, "invalid secret")
+                        }
+                    }
+                }
+                _ => {
                     eprintln!(
-                        "[state/reject] peer={peer} secret={} reason=invalid_secret",
-                        short_secret(secret)
+                        "[request/reject] peer={peer} method={method} path={path} reason=not_found"
                     );
-                    write_error(&mut stream, 401, "invalid secret")
+                    write_error(&mut stream, 404, "not found")
                 }
             }
         }
@@ -144,6 +319,263 @@ fn handle_connection(stream: TcpStream, host: Arc<Mutex<GameHost>>) -> Result<()
     }
 }
 
+fn serve_websocket(
+    reader: BufReader<TcpStream>,
+    key: &str,
+    shared: &Arc<Shared>,
+) -> Result<(), String> {
+    let mut stream = reader
+        .get_ref()
+        .try_clone()
+        .map_err(|err| err.to_string())?;
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        ws::accept_key(key)
+    )
+    .map_err(|err| err.to_string())?;
+    let peer = peer_addr(&stream);
+    eprintln!("[ws/open] peer={peer}");
+
+    let mut session = WsSession {
+        shared: Arc::clone(shared),
+        writer: Arc::new(Mutex::new(stream)),
+        closed: Arc::new(AtomicBool::new(false)),
+        peer: peer.clone(),
+        seat: None,
+        watching: None,
+        start_events: None,
+    };
+    let mut reader = MessageReader::new(reader, true);
+    let result = loop {
+        let sent = match reader.read_message() {
+            Ok(Message::Text(text)) => {
+                let reply = session.handle(&text);
+                let sent = session.send(&reply);
+                session.spawn_event_pusher();
+                sent
+            }
+            Ok(Message::Ping(payload)) => session.write_frame(ws::OPCODE_PONG, &payload),
+            Ok(Message::Close) => {
+                let _ = session.write_frame(ws::OPCODE_CLOSE, &[]);
+                break Ok(());
+            }
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = sent {
+            break Err(err.to_string());
+        }
+    };
+    // event を送っている thread を止める
+    session.closed.store(true, Ordering::SeqCst);
+    shared.moved.notify_all();
+    eprintln!("[ws/close] peer={peer}");
+    result
+}
+
+// WebSocket 1 本ぶんの状態。join したら、そのゲームの event を別の thread で送る
+struct WsSession {
+    shared: Arc<Shared>,
+    writer: Arc<Mutex<TcpStream>>,
+    closed: Arc<AtomicBool>,
+    peer: String,
+    // (ゲーム, secret)
+    seat: Option<(GameId, String)>,
+    // watch しているゲーム
+    watching: Option<GameId>,
+    // join や最初の watch の返事を送ってから、この番号からの event を送り始める
+    start_events: Option<(GameId, usize)>,
+}
+
+impl WsSession {
+    fn handle(&mut self, text: &str) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => return ws_error(format!("invalid message: {err}")),
+        };
+        let Ok(mut lobby) = self.shared.lobby.lock() else {
+            return ws_error("lock poisoned".to_string());
+        };
+        let peer = &self.peer;
+        match message {
+            ClientMessage::Join { game, secret } => {
+                if self.seat.is_some() || self.watching.is_some() {
+                    return ws_error("already joined".to_string());
+                }
+                let Some(host) = lobby.host_mut(game) else {
+                    return ws_error("unknown game".to_string());
+                };
+                let response = match &secret {
+                    Some(secret) => host.rejoin(secret).ok_or("invalid secret"),
+                    None => host.join().ok_or("all players are already joined"),
+                };
+                let response = match response {
+                    Ok(response) => response,
+                    Err(err) => {
+                        eprintln!("[ws/join/reject] peer={peer} game={game} reason={err}");
+                        return ws_error(err.to_string());
+                    }
+                };
+                eprintln!(
+                    "[ws/join] peer={peer} game={game} player={} secret={}",
+                    response.player,
+                    short_secret(&response.secret)
+                );
+                self.start_events = Some((game, host.events_since(0).next));
+                self.seat = Some((game, response.secret.clone()));
+                ServerMessage::Join(response)
+            }
+            ClientMessage::Watch { game, secret } => {
+                if self.seat.is_some() {
+                    return ws_error("already joined".to_string());
+                }
+                if self.watching.is_some_and(|watching| watching != game) {
+                    return ws_error("already watching another game".to_string());
+                }
+                let Some(host) = lobby.host(game) else {
+                    return ws_error("unknown game".to_string());
+                };
+                let Some(response) = host.watch(secret.as_deref()) else {
+                    eprintln!("[ws/watch/reject] peer={peer} game={game} reason=invalid_secret");
+                    return ws_error("missing or invalid secret".to_string());
+                };
+                if self.watching.is_none() {
+                    eprintln!(
+                        "[ws/watch] peer={peer} game={game} role={:?}",
+                        response.role
+                    );
+                    self.start_events = Some((game, host.events_since(0).next));
+                    self.watching = Some(game);
+                }
+                ServerMessage::Watch(response)
+            }
+            ClientMessage::State => {
+                let Some((game, secret)) = &self.seat else {
+                    return ws_error("join a game first".to_string());
+                };
+                lobby
+                    .host(*game)
+                    .and_then(|host| host.state_for_secret(secret))
+                    .map(ServerMessage::State)
+                    .unwrap_or_else(|| ws_error("unknown game".to_string()))
+            }
+            ClientMessage::Move(MoveRequest { action }) => {
+                let Some((game, secret)) = &self.seat else {
+                    return ws_error("join a game first".to_string());
+                };
+                let Some(host) = lobby.host_mut(*game) else {
+                    return ws_error("unknown game".to_string());
+                };
+                match host.apply_move(secret, action.clone()) {
+                    Ok(response) => {
+                        eprintln!(
+                            "[ws/move] peer={peer} game={game} secret={} action={}",
+                            short_secret(secret),
+                            format_move(&action)
+                        );
+                        if let Some(winner) = &response.winner {
+                            eprintln!("[win] game={game} winner={winner:?}");
+                        }
+                        self.shared.moved.notify_all();
+                        ServerMessage::Move(response)
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "[ws/move/reject] peer={peer} game={game} secret={} action={} reason={err}",
+                            short_secret(secret),
+                            format_move(&action)
+                        );
+                        ws_error(err)
+                    }
+                }
+            }
+        }
+    }
+
+    fn send(&self, message: &ServerMessage) -> io::Result<()> {
+        send_ws(&self.writer, message)
+    }
+
+    fn write_frame(&self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| io::Error::other("lock poisoned"))?;
+        ws::write_frame(&mut *writer, opcode, payload, None)
+    }
+
+    fn spawn_event_pusher(&mut self) {
+        let Some((game, since)) = self.start_events.take() else {
+            return;
+        };
+        let shared = Arc::clone(&self.shared);
+        let writer = Arc::clone(&self.writer);
+        let closed = Arc::clone(&self.closed);
+        std::thread::spawn(move || push_events(&shared, &writer, &closed, game, since));
+    }
+}
+
+// 決着の event を送るか、接続が閉じるか、ゲームが消えるまで送り続ける
+fn push_events(
+    shared: &Shared,
+    writer: &Mutex<TcpStream>,
+    closed: &AtomicBool,
+    game: GameId,
+    mut since: usize,
+) {
+    loop {
+        let events = {
+            let Ok(mut lobby) = shared.lobby.lock() else {
+                return;
+            };
+            loop {
+                if closed.load(Ordering::SeqCst) {
+                    return;
+                }
+                let Some(host) = lobby.host(game) else {
+                    return;
+                };
+                let response = host.events_since(since);
+                if !response.events.is_empty() {
+                    break response;
+                }
+                let Ok((next, _)) = shared.moved.wait_timeout(lobby, Duration::from_secs(1)) else {
+                    return;
+                };
+                lobby = next;
+            }
+        };
+        since = events.next;
+        for event in events.events {
+            let end = matches!(event, GameEvent::GameEnd { .. });
+            if send_ws(writer, &ServerMessage::Event { event }).is_err() || end {
+                return;
+            }
+        }
+    }
+}
+
+fn send_ws(writer: &Mutex<TcpStream>, message: &ServerMessage) -> io::Result<()> {
+    let text = serde_json::to_string(message).map_err(io::Error::other)?;
+    let mut writer = writer
+        .lock()
+        .map_err(|_| io::Error::other("lock poisoned"))?;
+    ws::write_text(&mut *writer, &text)
+}
+
+fn ws_error(error: String) -> ServerMessage {
+    ServerMessage::Error(ErrorResponse { error })
+}
+
+fn peer_addr(stream: &TcpStream) -> String {
+    stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| "<unknown>".to_string())
+}
+
 fn write_json<T: serde::Serialize>(
     stream: &mut TcpStream,
     status: u16,
@@ -171,6 +603,7 @@ fn write_response(
         200 => "OK",
         400 => "Bad Request",
         401 => "Unauthorized",
+        403 => "Forbidden",
         404 => "Not Found",
         409 => "Conflict",
         _ => "Internal Server Error",
@@ -190,6 +623,14 @@ fn usage_and_exit(message: &str) -> ! {
     std::process::exit(2);
 }
 
+fn query_param(query: &str, name: &str) -> Option<u64> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse::<u64>().ok())
+}
+
 fn short_secret(secret: &str) -> &str {
     let len = secret.len().min(8);
     &secret[..len]
@@ -1,7 +1,11 @@
-use crate::{JoinResponse, MoveResponse, StateResponse};
+use crate::{
+    format_move_ans, EventsResponse, GameEvent, JoinResponse, MoveResponse, StateResponse,
+    WatchResponse, WatchRole,
+};
 use game_core::{
     abstract_game::{Agent, ImperfectInfoGame},
-    defs::{Game, GameConfig, Info, Move},
+    commitment::{commit, Commitment, Reveal},
+    defs::{Game, GameConfig, Info, Move, DEAL_VERSION},
 };
 use rand::random;
 use std::sync::{Arc, Mutex};
@@ -9,20 +13,165 @@ use std::sync::{Arc, Mutex};
 #[derive(Debug)]
 pub struct GameHost {
     game: Game,
+    seed: u64,
+    salt: String,
+    // 作ったときに公開する。決着したら reveal で seed と salt を見せる
+    commitment: Commitment,
+    // seed を作った人が決めたか。そうなら作った人は配り方を全部知っている
+    creator_seeded: bool,
+    // 席を持たずに見るための token。referee は配り方も見える
+    spectator_token: String,
+    referee_token: Option<String>,
     joined: Vec<Option<String>>,
+    // run_turn で動かす AI に、履歴を何手目まで渡したか。None ならまだ on_game_start していない。
+    observed: Vec<Option<usize>>,
 }
 
 impl GameHost {
     pub fn new(config: GameConfig) -> Self {
-        let game = config.gen_random(random());
+        Self::build(config, random(), false)
+    }
+
+    // 配り方を seed で決める。seed を知っている人には配り方が筒抜けなので、join の返事で知らせる
+    pub fn with_seed(config: GameConfig, seed: u64) -> Self {
+        Self::build(config, seed, true)
+    }
+
+    fn build(config: GameConfig, seed: u64, creator_seeded: bool) -> Self {
+        let salt = format!("{:032x}", random::<u128>());
+        let commitment = commit(&config, seed, &salt);
+        let game = config.gen_random(seed as usize);
         let joined = vec![None; game.player_number()];
-        Self { game, joined }
+        let observed = vec![None; game.player_number()];
+        Self {
+            game,
+            seed,
+            salt,
+            commitment,
+            creator_seeded,
+            spectator_token: generate_token(),
+            referee_token: None,
+            joined,
+            observed,
+        }
+    }
+
+    pub fn creator_seeded(&self) -> bool {
+        self.creator_seeded
+    }
+
+    // referee がいれば、その人は席を取っていても配り方が見える
+    pub fn has_referee(&self) -> bool {
+        self.referee_token.is_some()
+    }
+
+    pub fn spectator_token(&self) -> &str {
+        &self.spectator_token
+    }
+
+    // referee の token を作る（作ってあればそれを返す）
+    pub fn referee_token(&mut self) -> String {
+        self.referee_token
+            .get_or_insert_with(generate_token)
+            .clone()
     }
 
     pub fn player_num(&self) -> usize {
         self.game.player_number()
     }
 
+    pub fn joined_num(&self) -> usize {
+        self.joined.iter().filter(|joined| joined.is_some()).count()
+    }
+
+    // 今までに指された手の数
+    pub fn move_num(&self) -> usize {
+        self.game.history().len()
+    }
+
+    pub fn winner(&self) -> Option<Vec<usize>> {
+        self.game.is_win()
+    }
+
+    pub fn commitment(&self) -> &Commitment {
+        &self.commitment
+    }
+
+    // 決着するまでは None
+    pub fn reveal(&self) -> Option<Reveal> {
+        self.game.is_win()?;
+        Some(Reveal {
+            deal_version: DEAL_VERSION,
+            config: self.game.config(),
+            seed: self.seed,
+            salt: self.salt.clone(),
+            distr: self.game.distr(),
+            history: self.game.history(),
+        })
+    }
+
+    // since 番目からの出来事。履歴から作るので、誰でも見てよいものしか入らない
+    pub fn events_since(&self, since: usize) -> EventsResponse {
+        let config = self.game.config();
+        let history = self.game.history();
+        let moves = history.len();
+        let mut events: Vec<GameEvent> = history
+            .into_iter()
+            .enumerate()
+            .skip(since)
+            .map(|(index, move_ans)| GameEvent::Move {
+                index,
+                move_ans,
+                current_turn: config.player_turn(index + 1),
+            })
+            .collect();
+        let Some(winner) = self.game.is_win() else {
+            return EventsResponse {
+                events,
+                next: moves,
+            };
+        };
+        if since <= moves {
+            events.push(GameEvent::GameEnd {
+                index: moves,
+                winner,
+            });
+        }
+        EventsResponse {
+            events,
+            next: moves + 1,
+        }
+    }
+
+    // secret が誰のものか。token がなくても、決着したあとは誰でも Public で見られる
+    pub fn watch_role(&self, secret: Option<&str>) -> Option<WatchRole> {
+        match secret {
+            Some(secret) if self.referee_token.as_deref() == Some(secret) => {
+                Some(WatchRole::Referee)
+            }
+            Some(secret) if secret == self.spectator_token => Some(WatchRole::Spectator),
+            Some(secret) if self.player_from_secret(secret).is_some() => Some(WatchRole::Player),
+            _ if self.game.is_win().is_some() => Some(WatchRole::Public),
+            _ => None,
+        }
+    }
+
+    pub fn watch(&self, secret: Option<&str>) -> Option<WatchResponse> {
+        let role = self.watch_role(secret)?;
+        let winner = self.game.is_win();
+        let query_answer = self.game.history();
+        let distr = (role == WatchRole::Referee || winner.is_some()).then(|| self.game.distr());
+        Some(WatchResponse {
+            role,
+            current_turn: self.game.player_turn(),
+            winner,
+            config: self.game.config(),
+            history: query_answer.iter().map(format_move_ans).collect(),
+            query_answer,
+            distr,
+        })
+    }
+
     pub fn join(&mut self) -> Option<JoinResponse> {
         let player = self.joined.iter().position(|joined| joined.is_none())?;
         let secret = self.generate_secret();
@@ -31,6 +180,22 @@ impl GameHost {
             player,
             player_num: self.player_num(),
             secret,
+            commitment: self.commitment.clone(),
+            creator_seeded: self.creator_seeded,
+            has_referee: self.has_referee(),
+        })
+    }
+
+    // 取った席にもう一度つなぐ（WebSocket をつなぎ直したときなど）
+    pub fn rejoin(&self, secret: &str) -> Option<JoinResponse> {
+        let player = self.player_from_secret(secret)?;
+        Some(JoinResponse {
+            player,
+            player_num: self.player_num(),
+            secret: secret.to_string(),
+            commitment: self.commitment.clone(),
+            creator_seeded: self.creator_seeded,
+            has_referee: self.has_referee(),
         })
     }
 
@@ -91,9 +256,42 @@ impl GameHost {
         if self.game.player_turn() != player {
             return Err(format!("not player {player}'s turn"));
         }
+        self.sync_agent(secret, agent)?;
         let (info, possible_moves) = self.game.info_and_move_now();
         let action = agent.use_info(info, possible_moves);
-        self.apply_move(secret, action)
+        let response = self.apply_move(secret, action)?;
+        self.sync_agent(secret, agent)?;
+        Ok(response)
+    }
+
+    // agent にまだ渡していない手を observe で渡す。最初に呼ばれたときは on_game_start も呼び、
+    // 決着がついていれば on_game_end も呼ぶ。run_turn の前後で呼ばれるので、
+    // 手番以外で決着したときに終了を伝えたければ、これを直接呼ぶ。
+    pub fn sync_agent(
+        &mut self,
+        secret: &str,
+        agent: &mut dyn Agent<Game = Game>,
+    ) -> Result<(), String> {
+        let player = self
+            .player_from_secret(secret)
+            .ok_or_else(|| "invalid secret".to_string())?;
+        let history = self.game.history();
+        let observed = match self.observed[player] {
+            Some(observed) if observed == history.len() => return Ok(()),
+            Some(observed) => observed,
+            None => {
+                agent.on_game_start(&self.game.config(), player);
+                0
+            }
+        };
+        for move_ans in &history[observed..] {
+            agent.observe(move_ans);
+        }
+        self.observed[player] = Some(history.len());
+        if let Some(winner) = self.game.is_win() {
+            agent.on_game_end(&winner);
+        }
+        Ok(())
     }
 
     fn player_from_secret(&self, secret: &str) -> Option<usize> {
@@ -104,12 +302,19 @@ impl GameHost {
 
     fn generate_secret(&self) -> String {
         loop {
-            let candidate = format!("{:032x}{:032x}", random::<u128>(), random::<u128>());
-            if self.player_from_secret(&candidate).is_none() {
+            let candidate = generate_token();
+            if self.player_from_secret(&candidate).is_none()
+                && candidate != self.spectator_token
+                && self.referee_token.as_deref() != Some(&candidate)
+            {
                 return candidate;
             }
         }
     }
 }
 
+fn generate_token() -> String {
+    format!("{:032x}{:032x}", random::<u128>(), random::<u128>())
+}
+
 pub type SharedHost = Arc<Mutex<GameHost>>;
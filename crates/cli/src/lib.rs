@@ -1,11 +1,20 @@
-use game_core::defs::{Info, Move, MoveAns};
+use game_core::{
+    commitment::{Commitment, Reveal},
+    defs::{cards_from_player, Distr, GameConfig, Info, Move, MoveAns},
+};
 use serde::{Deserialize, Serialize};
 use std::{
     io::{self, BufRead, BufReader, Read, Write},
     net::TcpStream,
+    time::Duration,
 };
 
 pub mod host;
+pub mod lobby;
+pub mod ws;
+
+// server が持つゲームの番号
+pub type GameId = u64;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateResponse {
@@ -38,6 +47,19 @@ impl StateResponse {
             possible_moves,
         }
     }
+
+    // 公開された配り方が、この席から見えていたものと食い違わないか
+    pub fn check_reveal(&self, reveal: &Reveal) -> Result<(), String> {
+        if reveal.distr.len() <= self.you
+            || cards_from_player(&reveal.distr, self.you) != self.info.view
+        {
+            return Err("revealed distr differs from the cards you saw".to_string());
+        }
+        if reveal.history != self.info.query_answer {
+            return Err("revealed history differs from the history you saw".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +67,12 @@ pub struct JoinResponse {
     pub player: usize,
     pub player_num: usize,
     pub secret: String,
+    // 配り方の commitment。控えておけば、決着後に /reveal と突き合わせられる
+    pub commitment: Commitment,
+    // 作った人が seed を決めたゲーム。作った人は配り方を知っている
+    pub creator_seeded: bool,
+    // referee の token があるゲーム。token を持つ人も join できるので、配り方を知る参加者がいるかもしれない
+    pub has_referee: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,11 +86,135 @@ pub struct MoveResponse {
     pub winner: Option<Vec<usize>>,
 }
 
+// POST /games
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateGameRequest {
+    // config::by_name の名前。config_json もなければ three_midium
+    #[serde(default)]
+    pub config: Option<String>,
+    // 名前のない config。cli では --config-file で読んだもの
+    #[serde(default)]
+    pub config_json: Option<GameConfig>,
+    // config の人数を変える
+    #[serde(default)]
+    pub player_num: Option<usize>,
+    // 配り方の seed。決めておけば作った人があとで配り方を確かめられる。
+    // 作った人には配り方がわかってしまうので、参加者には creator_seeded で知らせる
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // 配り方を全部見られる referee の token も作る
+    #[serde(default)]
+    pub referee: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateGameResponse {
+    pub game_id: GameId,
+    pub player_num: usize,
+    pub commitment: Commitment,
+    // 観戦用。渡した人は公開されている履歴だけ見られる
+    pub spectator_token: String,
+    pub referee_token: Option<String>,
+}
+
+// GET /games の 1 行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub game_id: GameId,
+    pub config: String,
+    pub player_num: usize,
+    pub joined: usize,
+    pub commitment: Commitment,
+    pub creator_seeded: bool,
+    pub has_referee: bool,
+}
+
+// GET /games/{id}/watch で、誰として見ているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchRole {
+    Player,
+    Spectator,
+    Referee,
+    // token なし。決着したあとだけ
+    Public,
+}
+
+// 席を持たない人向けの状態。distr は referee か、決着したあとだけ入る
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchResponse {
+    pub role: WatchRole,
+    pub current_turn: usize,
+    pub winner: Option<Vec<usize>>,
+    pub config: GameConfig,
+    pub query_answer: Vec<MoveAns>,
+    pub history: Vec<String>,
+    pub distr: Option<Distr>,
+}
+
+// GET /games/{id}/events?since=N で返す出来事。index は 0 からの通し番号で、
+// 手は履歴の何手目かと同じ。決着したら最後に game_end が 1 つつく
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    // current_turn はこの手のあとに打つ人
+    Move {
+        index: usize,
+        move_ans: MoveAns,
+        current_turn: usize,
+    },
+    GameEnd {
+        index: usize,
+        winner: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsResponse {
+    pub events: Vec<GameEvent>,
+    // 次に聞くときの since
+    pub next: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
 }
 
+// GET /ws (WebSocket) で client が送る JSON。"type" で見分ける
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // secret を渡すと、前に取った席に戻る
+    Join {
+        game: GameId,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+    State,
+    Move(MoveRequest),
+    // 席を取らずに見る。secret は観戦用か referee の token（決着したあとならなくてよい）。
+    // もう一度送ると watch を送り直す
+    Watch {
+        game: GameId,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+}
+
+// GET /ws で server が送る JSON。join / state / move はそれぞれの要求への返事で、
+// event は join したゲームで何か起きるたびに送る
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Join(JoinResponse),
+    State(StateResponse),
+    Move(MoveResponse),
+    Watch(WatchResponse),
+    Event { event: GameEvent },
+    Error(ErrorResponse),
+}
+
 pub fn format_move_ans(value: &MoveAns) -> String {
     match value {
         MoveAns::Query {
@@ -77,6 +229,83 @@ pub fn format_move_ans(value: &MoveAns) -> String {
     }
 }
 
+// server の API を型つきで呼ぶ
+#[derive(Debug, Clone, Copy)]
+pub struct Client {
+    port: u16,
+}
+
+impl Client {
+    pub fn new(port: u16) -> Self {
+        Self { port }
+    }
+
+    pub fn create_game(&self, request: &CreateGameRequest) -> io::Result<CreateGameResponse> {
+        post_json(self.port, "/games", request, None)
+    }
+
+    // まだ席の空いているゲーム
+    pub fn open_games(&self) -> io::Result<Vec<GameSummary>> {
+        get_json(self.port, "/games", None)
+    }
+
+    pub fn join(&self, game: GameId) -> io::Result<JoinResponse> {
+        post_json(
+            self.port,
+            &format!("/games/{game}/join"),
+            &serde_json::json!({}),
+            None,
+        )
+    }
+
+    pub fn state(&self, game: GameId, secret: &str) -> io::Result<StateResponse> {
+        get_json(self.port, &format!("/games/{game}/state"), Some(secret))
+    }
+
+    // 決着したゲームの seed と配り方
+    pub fn reveal(&self, game: GameId) -> io::Result<Reveal> {
+        get_json(self.port, &format!("/games/{game}/reveal"), None)
+    }
+
+    // since 番目からの出来事。まだなければ server が timeout まで待ってから返す。
+    // 決着するまでは席の secret か観戦用の token がいる
+    pub fn events(
+        &self,
+        game: GameId,
+        secret: Option<&str>,
+        since: usize,
+        timeout: Duration,
+    ) -> io::Result<EventsResponse> {
+        get_json(
+            self.port,
+            &format!(
+                "/games/{game}/events?since={since}&timeout_ms={}",
+                timeout.as_millis()
+            ),
+            secret,
+        )
+    }
+
+    // 観戦用・referee の token か席の secret で見る。決着したあとは None でもよい
+    pub fn watch(&self, game: GameId, secret: Option<&str>) -> io::Result<WatchResponse> {
+        get_json(self.port, &format!("/games/{game}/watch"), secret)
+    }
+
+    pub fn submit_move(
+        &self,
+        game: GameId,
+        secret: &str,
+        action: Move,
+    ) -> io::Result<MoveResponse> {
+        post_json(
+            self.port,
+            &format!("/games/{game}/move"),
+            &MoveRequest { action },
+            Some(secret),
+        )
+    }
+}
+
 pub fn get_json<T: for<'de> Deserialize<'de>>(
     port: u16,
     path: &str,
@@ -0,0 +1,380 @@
+use crate::{host::GameHost, CreateGameRequest, CreateGameResponse, GameId, GameSummary};
+use game_core::config;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// server が持つゲームの一覧。POST /games で作り、番号で参加する。
+// 決着したゲームは keep_finished のあいだだけ残して（結果を見に来られるように）、そのあと消す。
+// 決着していなくても、idle_timeout のあいだ join も手もなければ（放置された・集まらない）消す。
+// 消すのは create / open_games のときと、spawn_sweeper で立てた thread が sweep_interval ごとに。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbyOptions {
+    // 同時に持つゲームの上限
+    pub max_games: usize,
+    pub keep_finished: Duration,
+    pub idle_timeout: Duration,
+}
+
+impl Default for LobbyOptions {
+    fn default() -> Self {
+        Self {
+            max_games: 64,
+            keep_finished: Duration::from_secs(600),
+            idle_timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl LobbyOptions {
+    // 短いほうの期限の 1/4 ごと。ただし 1 秒から 60 秒のあいだ
+    pub fn sweep_interval(&self) -> Duration {
+        (self.keep_finished.min(self.idle_timeout) / 4)
+            .clamp(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+#[derive(Debug)]
+struct HostedGame {
+    host: GameHost,
+    config_name: String,
+    // 決着に気づいた時刻
+    finished_at: Option<Instant>,
+    // 最後に join か手があったのに気づいた時刻と、そのときの (参加した人数, 手数)
+    active_at: Instant,
+    progress: (usize, usize),
+}
+
+#[derive(Debug)]
+pub struct Lobby {
+    options: LobbyOptions,
+    games: BTreeMap<GameId, HostedGame>,
+    next_id: GameId,
+}
+
+impl Lobby {
+    pub fn new(options: LobbyOptions) -> Self {
+        Self {
+            options,
+            games: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn create(&mut self, request: CreateGameRequest) -> Result<CreateGameResponse, String> {
+        self.collect_garbage();
+        if self.games.len() >= self.options.max_games {
+            return Err(format!("too many games (max {})", self.options.max_games));
+        }
+        let (config_name, config) = match (request.config, request.config_json) {
+            (Some(_), Some(_)) => return Err("use either config or config_json".to_string()),
+            (None, Some(config)) => ("custom".to_string(), config),
+            (name, None) => {
+                let name = name.unwrap_or_else(|| "three_midium".to_string());
+                let config =
+                    config::by_name(&name).ok_or_else(|| format!("unknown config: {name}"))?;
+                (name, config)
+            }
+        };
+        let player_num = request.player_num.unwrap_or(config.player_num());
+        if player_num < 2 {
+            return Err("player_num must be at least 2".to_string());
+        }
+        let config = config
+            .with_player_num(player_num)
+            .ok_or("not enough cards for the players")?;
+
+        let mut host = match request.seed {
+            Some(seed) => GameHost::with_seed(config, seed),
+            None => GameHost::new(config),
+        };
+        let commitment = host.commitment().clone();
+        let spectator_token = host.spectator_token().to_string();
+        let referee_token = request.referee.then(|| host.referee_token());
+        let game_id = self.next_id;
+        self.next_id += 1;
+        self.games.insert(
+            game_id,
+            HostedGame {
+                host,
+                config_name,
+                finished_at: None,
+                active_at: Instant::now(),
+                progress: (0, 0),
+            },
+        );
+        Ok(CreateGameResponse {
+            game_id,
+            player_num,
+            commitment,
+            spectator_token,
+            referee_token,
+        })
+    }
+
+    // まだ席の空いているゲーム
+    pub fn open_games(&mut self) -> Vec<GameSummary> {
+        self.collect_garbage();
+        self.games
+            .iter()
+            .filter(|(_, game)| {
+                game.host.joined_num() < game.host.player_num() && game.host.winner().is_none()
+            })
+            .map(|(game_id, game)| GameSummary {
+                game_id: *game_id,
+                config: game.config_name.clone(),
+                player_num: game.host.player_num(),
+                joined: game.host.joined_num(),
+                commitment: game.host.commitment().clone(),
+                creator_seeded: game.host.creator_seeded(),
+                has_referee: game.host.has_referee(),
+            })
+            .collect()
+    }
+
+    pub fn host(&self, game_id: GameId) -> Option<&GameHost> {
+        self.games.get(&game_id).map(|game| &game.host)
+    }
+
+    pub fn host_mut(&mut self, game_id: GameId) -> Option<&mut GameHost> {
+        self.games.get_mut(&game_id).map(|game| &mut game.host)
+    }
+
+    // 決着してから keep_finished より経ったゲームと、決着せずに idle_timeout より動きのないゲームを消し、
+    // 消した番号を返す
+    pub fn collect_garbage(&mut self) -> Vec<GameId> {
+        let now = Instant::now();
+        for game in self.games.values_mut() {
+            if game.finished_at.is_none() && game.host.winner().is_some() {
+                game.finished_at = Some(now);
+            }
+            let progress = (game.host.joined_num(), game.host.move_num());
+            if progress != game.progress {
+                game.progress = progress;
+                game.active_at = now;
+            }
+        }
+        let LobbyOptions {
+            keep_finished,
+            idle_timeout,
+            ..
+        } = self.options;
+        let expired: Vec<GameId> = self
+            .games
+            .iter()
+            .filter(|(_, game)| match game.finished_at {
+                Some(finished_at) => now.duration_since(finished_at) >= keep_finished,
+                None => now.duration_since(game.active_at) >= idle_timeout,
+            })
+            .map(|(game_id, _)| *game_id)
+            .collect();
+        for game_id in &expired {
+            self.games.remove(game_id);
+        }
+        expired
+    }
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Self::new(LobbyOptions::default())
+    }
+}
+
+// 誰も create や一覧を呼ばなくても期限の過ぎたゲームが消えるように、interval ごとに collect_garbage する。
+// lobby を持つ Arc がなくなるか、lock が壊れたら止まる
+pub fn spawn_sweeper(lobby: &Arc<Mutex<Lobby>>, interval: Duration) -> JoinHandle<()> {
+    let lobby = Arc::downgrade(lobby);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let Some(lobby) = lobby.upgrade() else {
+            return;
+        };
+        let Ok(mut lobby) = lobby.lock() else {
+            return;
+        };
+        lobby.collect_garbage();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameEvent, WatchRole};
+    use game_core::{commitment::verify_reveal, defs::Move};
+
+    fn small_game(lobby: &mut Lobby) -> CreateGameResponse {
+        lobby
+            .create(CreateGameRequest {
+                config: Some("three_small".to_string()),
+                player_num: Some(2),
+                seed: Some(1),
+                referee: true,
+                ..Default::default()
+            })
+            .unwrap()
+    }
+
+    // 全員参加して、各席の secret を返す
+    fn join_all(host: &mut GameHost) -> Vec<String> {
+        (0..host.player_num())
+            .map(|_| host.join().unwrap().secret)
+            .collect()
+    }
+
+    // 決着するまで打つ。宣言を全部試せばいつか当たる
+    fn play_to_end(host: &mut GameHost, secrets: &[String]) {
+        while host.winner().is_none() {
+            let state = secrets
+                .iter()
+                .find_map(|secret| host.state_for_secret(secret).filter(|s| s.your_turn))
+                .unwrap();
+            let action = state
+                .possible_moves
+                .iter()
+                .find(|m| matches!(m, Move::Declare { .. }))
+                .unwrap_or(&state.possible_moves[0])
+                .clone();
+            host.apply_move(&secrets[state.you], action).unwrap();
+        }
+    }
+
+    #[test]
+    fn lobby_hosts_games_and_collects_finished_ones() {
+        let mut lobby = Lobby::new(LobbyOptions {
+            max_games: 2,
+            keep_finished: Duration::ZERO,
+            idle_timeout: Duration::from_secs(3600),
+        });
+        let small = small_game(&mut lobby);
+        assert_eq!(small.player_num, 2);
+        let other = lobby.create(CreateGameRequest::default()).unwrap();
+        assert_ne!(small.game_id, other.game_id);
+        assert!(lobby.create(CreateGameRequest::default()).is_err());
+        assert!(Lobby::default()
+            .create(CreateGameRequest {
+                config: Some("unknown".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+
+        // 全員参加すると一覧から消える
+        assert_eq!(lobby.open_games().len(), 2);
+        let secrets = join_all(lobby.host_mut(small.game_id).unwrap());
+        assert_eq!(lobby.open_games().len(), 1);
+
+        play_to_end(lobby.host_mut(small.game_id).unwrap(), &secrets);
+        assert_eq!(lobby.collect_garbage(), vec![small.game_id]);
+        assert!(lobby.host(small.game_id).is_none());
+        assert!(lobby.host(other.game_id).is_some());
+
+        // 動きのないまま idle_timeout が経つと、決着していなくても消える
+        let mut lobby = Lobby::new(LobbyOptions {
+            idle_timeout: Duration::ZERO,
+            ..Default::default()
+        });
+        let idle = lobby.create(CreateGameRequest::default()).unwrap();
+        assert_eq!(lobby.collect_garbage(), vec![idle.game_id]);
+    }
+
+    #[test]
+    fn sweeper_collects_without_lobby_calls() {
+        let lobby = Arc::new(Mutex::new(Lobby::new(LobbyOptions {
+            keep_finished: Duration::ZERO,
+            ..Default::default()
+        })));
+        let (game_id, secrets) = {
+            let mut lobby = lobby.lock().unwrap();
+            let small = small_game(&mut lobby);
+            let host = lobby.host_mut(small.game_id).unwrap();
+            let secrets = join_all(host);
+            (small.game_id, secrets)
+        };
+        let sweeper = spawn_sweeper(&lobby, Duration::from_millis(10));
+
+        // 決着させたあとは create も open_games も呼ばない
+        play_to_end(lobby.lock().unwrap().host_mut(game_id).unwrap(), &secrets);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lobby.lock().unwrap().host(game_id).is_some() {
+            assert!(
+                Instant::now() < deadline,
+                "the sweeper did not collect the game"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // lobby を捨てると sweeper も止まる
+        drop(lobby);
+        sweeper.join().unwrap();
+    }
+
+    #[test]
+    fn reveal_matches_the_commitment() {
+        let mut lobby = Lobby::default();
+        let small = small_game(&mut lobby);
+        let host = lobby.host_mut(small.game_id).unwrap();
+        let joined: Vec<_> = (0..2).map(|_| host.join().unwrap()).collect();
+        assert!(joined.iter().all(|j| j.commitment == small.commitment));
+        assert!(joined.iter().all(|j| j.creator_seeded));
+        let secrets: Vec<String> = joined.into_iter().map(|j| j.secret).collect();
+        assert!(host.reveal().is_none());
+
+        // 決着したら配り方を公開し、作ったときの commitment と各席の見え方に合う
+        play_to_end(host, &secrets);
+        let reveal = host.reveal().unwrap();
+        assert_eq!(reveal.seed, 1);
+        assert_eq!(verify_reveal(&small.commitment, &reveal), Ok(()));
+        for secret in &secrets {
+            let state = host.state_for_secret(secret).unwrap();
+            assert_eq!(state.check_reveal(&reveal), Ok(()));
+        }
+    }
+
+    #[test]
+    fn events_are_one_per_move_and_the_end() {
+        let mut lobby = Lobby::default();
+        let small = small_game(&mut lobby);
+        let host = lobby.host_mut(small.game_id).unwrap();
+        let secrets = join_all(host);
+        assert!(host.events_since(0).events.is_empty());
+
+        play_to_end(host, &secrets);
+        let events = host.events_since(0);
+        assert_eq!(events.next, host.reveal().unwrap().history.len() + 1);
+        assert!(matches!(
+            events.events.last(),
+            Some(GameEvent::GameEnd { .. })
+        ));
+        assert!(host.events_since(events.next).events.is_empty());
+    }
+
+    #[test]
+    fn watchers_see_what_their_token_allows() {
+        let mut lobby = Lobby::default();
+        let small = small_game(&mut lobby);
+        let host = lobby.host_mut(small.game_id).unwrap();
+        let joined: Vec<_> = (0..2).map(|_| host.join().unwrap()).collect();
+        assert!(joined.iter().all(|j| j.has_referee));
+        let secrets: Vec<String> = joined.into_iter().map(|j| j.secret).collect();
+
+        // 決着までは token がないと見られず、配り方は referee だけに見える
+        assert!(host.watch(None).is_none());
+        let spectator = host.watch(Some(&small.spectator_token)).unwrap();
+        assert_eq!(spectator.role, WatchRole::Spectator);
+        assert!(spectator.distr.is_none());
+        assert!(host.watch(Some(&secrets[0])).unwrap().distr.is_none());
+        let referee = host.watch(small.referee_token.as_deref()).unwrap();
+        assert_eq!(referee.role, WatchRole::Referee);
+        assert!(referee.distr.is_some());
+
+        // 決着したら誰でも全部見られる
+        play_to_end(host, &secrets);
+        let public = host.watch(None).unwrap();
+        assert_eq!(public.role, WatchRole::Public);
+        assert_eq!(public.distr, referee.distr);
+    }
+}
@@ -0,0 +1,282 @@
+use sha1::{Digest, Sha1};
+use std::io::{self, Read, Write};
+
+// server の GET /ws で使う、最小限の WebSocket (RFC 6455)。
+// text と ping / close だけ扱い、拡張やサブプロトコルは使わない。
+// client から server へのフレームは mask 必須（RFC 6455 5.1）なので、server 側では mask のないフレームを拒む。
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// 1 つのメッセージの上限。ゲームの JSON はこれよりずっと小さい
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+// Sec-WebSocket-Key に対して返す Sec-WebSocket-Accept
+pub fn accept_key(key: &str) -> String {
+    let digest = Sha1::digest(format!("{}{GUID}", key.trim()));
+    base64(&digest)
+}
+
+// ブラウザからの handshake なら Origin が server 自身（Host）か allowed にあるときだけ受ける。
+// allowed に "*" があればどこからでも受ける。Origin のない client（ブラウザでないもの）は受ける
+pub fn origin_allowed(origin: Option<&str>, host: Option<&str>, allowed: &[String]) -> bool {
+    let Some(origin) = origin.map(str::trim) else {
+        return true;
+    };
+    if allowed
+        .iter()
+        .any(|a| a == "*" || a.eq_ignore_ascii_case(origin))
+    {
+        return true;
+    }
+    let origin_host = origin
+        .split_once("://")
+        .map_or(origin, |(_, rest)| rest)
+        .trim_end_matches('/');
+    host.is_some_and(|host| host.trim().eq_ignore_ascii_case(origin_host))
+}
+
+// 分割されたメッセージをつなげて読む。途中に ping などが挟まってもよい
+#[derive(Debug)]
+pub struct MessageReader<R> {
+    inner: R,
+    partial: Option<(u8, Vec<u8>)>,
+    // server 側で client からのフレームを読むなら true
+    require_mask: bool,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(inner: R, require_mask: bool) -> Self {
+        Self {
+            inner,
+            partial: None,
+            require_mask,
+        }
+    }
+
+    pub fn read_message(&mut self) -> io::Result<Message> {
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut self.inner, self.require_mask)?;
+            match opcode {
+                OPCODE_CLOSE => return Ok(Message::Close),
+                OPCODE_PING => return Ok(Message::Ping(payload)),
+                OPCODE_PONG => return Ok(Message::Pong(payload)),
+                OPCODE_TEXT | OPCODE_BINARY if self.partial.is_none() => {
+                    self.partial = Some((opcode, payload));
+                }
+                OPCODE_CONTINUATION if self.partial.is_some() => {
+                    let (_, buffer) = self.partial.as_mut().unwrap();
+                    buffer.extend(payload);
+                }
+                _ => return Err(invalid_data(format!("unexpected opcode {opcode}"))),
+            }
+            if self.partial.as_ref().unwrap().1.len() > MAX_MESSAGE_LEN {
+                return Err(invalid_data("message too long".to_string()));
+            }
+            if fin {
+                let (opcode, payload) = self.partial.take().unwrap();
+                return if opcode == OPCODE_TEXT {
+                    String::from_utf8(payload)
+                        .map(Message::Text)
+                        .map_err(|err| invalid_data(err.to_string()))
+                } else {
+                    Ok(Message::Binary(payload))
+                };
+            }
+        }
+    }
+}
+
+fn read_frame(reader: &mut impl Read, require_mask: bool) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    if require_mask && !masked {
+        return Err(invalid_data("client frames must be masked".to_string()));
+    }
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(invalid_data("frame too long".to_string()));
+    }
+    let mask = if masked {
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask)?;
+        Some(mask)
+    } else {
+        None
+    };
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    if let Some(mask) = mask {
+        apply_mask(&mut payload, mask);
+    }
+    Ok((fin, opcode, payload))
+}
+
+// server から送るときは mask なし、client から送るときは mask をつける
+pub fn write_frame(
+    writer: &mut impl Write,
+    opcode: u8,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    let mut payload = payload.to_vec();
+    if let Some(mask) = mask {
+        frame.extend(mask);
+        apply_mask(&mut payload, mask);
+    }
+    frame.extend(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+pub fn write_text(writer: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(writer, OPCODE_TEXT, text.as_bytes(), None)
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_and_frames_follow_rfc() {
+        // RFC 6455 1.3 の例
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        // client からの mask つき・分割されたメッセージの間に ping が挟まる
+        let mask = Some([1, 2, 3, 4]);
+        let long = "x".repeat(300);
+        let mut wire = Vec::new();
+        wire.extend([0x01, 0x80 | 3, 1, 2, 3, 4]);
+        wire.extend(b"{\"a".iter().zip([1, 2, 3]).map(|(b, m)| b ^ m));
+        write_frame(&mut wire, OPCODE_PING, b"hi", mask).unwrap();
+        write_frame(&mut wire, OPCODE_CONTINUATION, b"\":1}", mask).unwrap();
+        write_frame(&mut wire, OPCODE_TEXT, long.as_bytes(), mask).unwrap();
+        write_frame(&mut wire, OPCODE_CLOSE, &[], mask).unwrap();
+
+        let mut reader = MessageReader::new(wire.as_slice(), true);
+        assert_eq!(
+            reader.read_message().unwrap(),
+            Message::Ping(b"hi".to_vec())
+        );
+        assert_eq!(
+            reader.read_message().unwrap(),
+            Message::Text("{\"a\":1}".to_string())
+        );
+        assert_eq!(reader.read_message().unwrap(), Message::Text(long));
+        assert_eq!(reader.read_message().unwrap(), Message::Close);
+        assert!(reader.read_message().is_err());
+
+        // server からの mask のないフレームは client では読めるが、server では拒む
+        let mut wire = Vec::new();
+        write_text(&mut wire, "{}").unwrap();
+        let mut client = MessageReader::new(wire.as_slice(), false);
+        assert_eq!(
+            client.read_message().unwrap(),
+            Message::Text("{}".to_string())
+        );
+        let mut server = MessageReader::new(wire.as_slice(), true);
+        assert_eq!(
+            server.read_message().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn origin_must_be_the_server_or_allowed() {
+        let host = Some("127.0.0.1:8080");
+        assert!(origin_allowed(None, host, &[]));
+        assert!(origin_allowed(Some("http://127.0.0.1:8080"), host, &[]));
+        assert!(!origin_allowed(Some("http://evil.example"), host, &[]));
+        assert!(!origin_allowed(Some("http://127.0.0.1:8080"), None, &[]));
+        let allowed = vec!["http://localhost:3000".to_string()];
+        assert!(origin_allowed(
+            Some("http://localhost:3000"),
+            host,
+            &allowed
+        ));
+        assert!(!origin_allowed(
+            Some("http://localhost:4000"),
+            host,
+            &allowed
+        ));
+        assert!(origin_allowed(
+            Some("http://evil.example"),
+            host,
+            &["*".to_string()]
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use game_ai_entropy::entoropy;
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame, Player},
+    defs::*,
+    utils::*,
+};
+
+// 相手の query の選び方から、相手の手札を推測する。
+// possible_states の各配置について、その配置で相手が実際の query を選ぶ確率（尤度）を掛けて重みにする。
+// 相手の view は「自分の頭」と「相手の手札」以外は自分にも見えているので、配置ごとに作れる。
+// 宣言の結果は possible_states ですでに絞り込まれているので、尤度は query の手にだけ掛ける。
+
+pub trait QueryModel {
+    // info の持ち主が query m を選ぶ確率
+    fn likelihood(&self, info: &Info, m: &Move) -> f64;
+}
+
+// 決定的な方策に、確率 noise で一様ランダムな query が混ざるモデル。
+// どの AI も頭が確定していれば宣言するので、確定している配置で query したなら noise の分だけにする。
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoisyPolicy<F> {
+    policy: F,
+    noise: f64,
+}
+
+impl<F> NoisyPolicy<F>
+where
+    F: Fn(Info) -> Option<Move>,
+{
+    pub fn new(policy: F, noise: f64) -> Self {
+        assert!((0_f64..=1_f64).contains(&noise));
+        Self { policy, noise }
+    }
+}
+
+impl<F> QueryModel for NoisyPolicy<F>
+where
+    F: Fn(Info) -> Option<Move>,
+{
+    fn likelihood(&self, info: &Info, m: &Move) -> f64 {
+        let n = info.movable_query().len().max(1) as f64;
+        let uniform = self.noise / n;
+        if answerable_info(info).is_some() {
+            return uniform;
+        }
+        match (self.policy)(info.clone()) {
+            Some(chosen) if chosen == *m => 1_f64 - self.noise + uniform,
+            Some(_) => uniform,
+            // 方策が query を選ばない局面（宣言するはず）なら区別がつかないので一様
+            None => 1_f64 / n,
+        }
+    }
+}
+
+pub type EntropyModel = NoisyPolicy<fn(Info) -> Option<Move>>;
+
+pub fn entropy_model(noise: f64) -> EntropyModel {
+    NoisyPolicy::new(entoropy as fn(Info) -> Option<Move>, noise)
+}
+
+// (それまでの履歴, 相手の手札, 相手の見ている他人の頭)
+type LikelihoodKey = (Vec<MoveAns>, BTreeSet<Card>, Vec<Option<BTreeSet<Card>>>);
+
+// 尤度は同じ履歴・同じ相手の view なら変わらないので、ゲームを通して覚えておく。
+#[derive(Debug, Clone)]
+pub struct Belief<M> {
+    model: M,
+    cache: HashMap<LikelihoodKey, f64>,
+}
+
+impl<M> Belief<M>
+where
+    M: QueryModel,
+{
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    // 手番のプレイヤーから見た可能な配置と、その確率。確率の和は 1 になる。
+    pub fn weighted_states(&mut self, info: &Info) -> Vec<(Distr, f64)> {
+        let me = info.player_turn();
+        let mut states: Vec<(Distr, f64)> =
+            possible_states(&info.config, &info.query_answer, &info.view)
+                .map(|distr| (distr, 1_f64))
+                .collect();
+
+        for (t, qa) in info.query_answer.iter().enumerate() {
+            let MoveAns::Query { who, .. } = qa else {
+                continue;
+            };
+            if *who == me {
+                continue;
+            }
+            let m = qa.move_of_this();
+            for (distr, weight) in &mut states {
+                let view = cards_from_player(distr, *who);
+                let key = (
+                    info.query_answer[..t].to_vec(),
+                    view.hand.clone(),
+                    view.other.clone(),
+                );
+                let likelihood = *self.cache.entry(key).or_insert_with(|| {
+                    let info = Info {
+                        config: info.config.clone(),
+                        query_answer: info.query_answer[..t].to_vec(),
+                        view,
+                    };
+                    self.model.likelihood(&info, &m)
+                });
+                *weight *= likelihood;
+            }
+        }
+
+        let total: f64 = states.iter().map(|(_, w)| w).sum();
+        let n = states.len() as f64;
+        for (_, weight) in &mut states {
+            // モデルが全部の配置を否定したら、推測は諦めて一様にする
+            *weight = if total > 0_f64 {
+                *weight / total
+            } else {
+                1_f64 / n
+            };
+        }
+        states
+    }
+}
+
+pub fn weighted_states<M>(info: &Info, model: M) -> Vec<(Distr, f64)>
+where
+    M: QueryModel,
+{
+    Belief::new(model).weighted_states(info)
+}
+
+// UseEntropyPlayer と同じ方針を、重みつきの配置で行う。
+// 回答の分布のエントロピーが一番大きい query を選び、情報が得られる query がなければ一番ありそうな頭を宣言する。
+#[derive(Debug, Clone)]
+pub struct BayesPlayer<M> {
+    belief: Belief<M>,
+}
+
+impl<M> BayesPlayer<M>
+where
+    M: QueryModel,
+{
+    pub fn new(model: M) -> Self {
+        Self {
+            belief: Belief::new(model),
+        }
+    }
+}
+
+impl Default for BayesPlayer<EntropyModel> {
+    fn default() -> Self {
+        Self::new(entropy_model(0.1))
+    }
+}
+
+impl<M> Agent for BayesPlayer<M>
+where
+    M: QueryModel + Send,
+{
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        if let Some(answer) = answerable_info(&info) {
+            return answer;
+        }
+
+        let who = info.player_turn();
+        let states = self.belief.weighted_states(&info);
+        debug_assert!(!states.is_empty());
+
+        let best_query = info
+            .movable_query()
+            .into_iter()
+            .filter_map(|q| {
+                let mut distribution = vec![0_f64; info.config.cards_num()];
+                for (distr, weight) in &states {
+                    let MoveAns::Query { ans, .. } = answer(&info.config, distr, q.clone(), who)
+                    else {
+                        unreachable!()
+                    };
+                    distribution[ans] += weight;
+                }
+                let entropy: f64 = distribution
+                    .iter()
+                    .filter(|p| **p > 0_f64)
+                    .map(|p| -p * p.log2())
+                    .sum();
+                if distribution.iter().filter(|p| **p > 0_f64).count() > 1 {
+                    Some((entropy, q))
+                } else {
+                    None
+                }
+            })
+            .max_by(|(entropy1, _), (entropy2, _)| entropy1.partial_cmp(entropy2).unwrap());
+        if let Some((_, q)) = best_query {
+            return q;
+        }
+
+        // 重みが一番大きい頭を宣言する。同じ重みなら小さい手にして、実行ごとに変わらないようにする
+        let possible_declare = info.movable_declare();
+        let mut heads: BTreeMap<Move, f64> = BTreeMap::new();
+        for (distr, weight) in &states {
+            let head = Move::Declare {
+                declare: players_head(distr, who).clone(),
+            };
+            if possible_declare.contains(&head) {
+                *heads.entry(head).or_default() += weight;
+            }
+        }
+        heads
+            .into_iter()
+            .max_by(|(m1, p1), (m2, p2)| p1.partial_cmp(p2).unwrap().then(m2.cmp(m1)))
+            .unwrap()
+            .0
+    }
+
+    fn on_game_start(&mut self, _config: &GameConfig, _seat: Player) {
+        self.belief.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_ai_entropy::UseEntropyPlayer;
+    use game_core::{config::three_small, utils::auto_game};
+
+    use super::*;
+
+    #[test]
+    fn belief_prefers_worlds_where_opponent_query_was_informative() {
+        let config = three_small();
+        let mut game = config.gen_random(3);
+        // 相手 2 人は entropy で打つので、モデルが正しい
+        let mut opponent = UseEntropyPlayer::default();
+        for _ in 0..3 {
+            let (info, moves) = game.info_and_move_now();
+            game.move_game(opponent.use_info(info, moves));
+        }
+
+        let (info, _) = game.info_and_move_now();
+        let states = weighted_states(&info, entropy_model(0.1));
+        let total: f64 = states.iter().map(|(_, w)| w).sum();
+        assert!((total - 1_f64).abs() < 1e-9);
+        let truth = states
+            .iter()
+            .find(|(distr, _)| *distr == game.distr())
+            .unwrap();
+        // 本当の配置は、モデルに矛盾しないので平均以上の重みを持つ
+        assert!(truth.1 >= 1_f64 / states.len() as f64 - 1e-9);
+
+        let agents: Vec<Box<dyn Agent<Game = Game>>> = vec![
+            Box::new(BayesPlayer::default()),
+            Box::new(UseEntropyPlayer::default()),
+            Box::new(UseEntropyPlayer::default()),
+        ];
+        let winner = auto_game(config.gen_random(1), agents);
+        assert_eq!(winner.iter().sum::<usize>(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame, Player},
+    defs::*,
+    utils::*,
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// 小さい config（three_small くらい）のための CFR。
+// - chance node は gen_random による配り方で、毎回ひとつサンプルする（chance sampling）。
+// - 情報集合は Info と同じく (view, 履歴) で区別する。
+// - 手番のプレイヤーの手は external sampling で全部展開し、他のプレイヤーの手はその時点の戦略からサンプルする。
+// - ゲームは有限だが長いので、max_turns 手で決着がつかなければ全員 0 点とする。
+// 3 人以上だと CFR の平均戦略がナッシュ均衡に収束する保証はないので、あくまで近似。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CfrVariant {
+    Vanilla,
+    // regret を 0 で切り、平均戦略は反復回数で線形に重みづけする
+    Plus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct InfoNode {
+    actions: Vec<Move>,
+    regret: Vec<f64>,
+    strategy_sum: Vec<f64>,
+}
+
+impl InfoNode {
+    fn new(actions: Vec<Move>) -> Self {
+        let n = actions.len();
+        Self {
+            actions,
+            regret: vec![0_f64; n],
+            strategy_sum: vec![0_f64; n],
+        }
+    }
+
+    // regret matching
+    fn current_strategy(&self) -> Vec<f64> {
+        normalize(self.regret.iter().map(|r| r.max(0_f64)).collect())
+    }
+
+    fn average_strategy(&self) -> Vec<f64> {
+        normalize(self.strategy_sum.clone())
+    }
+}
+
+fn normalize(mut v: Vec<f64>) -> Vec<f64> {
+    let sum: f64 = v.iter().sum();
+    if sum > 0_f64 {
+        for x in &mut v {
+            *x /= sum;
+        }
+    } else {
+        let n = v.len() as f64;
+        for x in &mut v {
+            *x = 1_f64 / n;
+        }
+    }
+    v
+}
+
+pub fn info_key(view: &View, query_answer: &[MoveAns]) -> String {
+    serde_json::to_string(&(view, query_answer)).expect("failed to serialize info set")
+}
+
+#[derive(Debug, Clone)]
+pub struct CfrSolver {
+    config: GameConfig,
+    max_turns: usize,
+    variant: CfrVariant,
+    nodes: HashMap<String, InfoNode>,
+    rng: SmallRng,
+    iteration: usize,
+}
+
+impl CfrSolver {
+    pub fn new(config: GameConfig, max_turns: usize, variant: CfrVariant, seed: u64) -> Self {
+        Self {
+            config,
+            max_turns,
+            variant,
+            nodes: HashMap::new(),
+            rng: SmallRng::seed_from_u64(seed),
+            iteration: 0,
+        }
+    }
+
+    pub fn info_set_num(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn run(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            self.iteration += 1;
+            let distr = self.config.gen_random(self.rng.gen()).distr();
+            for traverser in 0..self.config.player_num() {
+                let mut query_answer = vec![];
+                self.traverse(&distr, &mut query_answer, traverser);
+            }
+        }
+    }
+
+    // traverser から見た期待利得
+    fn traverse(
+        &mut self,
+        distr: &Distr,
+        query_answer: &mut Vec<MoveAns>,
+        traverser: Player,
+    ) -> f64 {
+        if let Some(MoveAns::Declare { who, ans: true, .. }) = query_answer.last() {
+            return if *who == traverser { 1_f64 } else { 0_f64 };
+        }
+        if query_answer.len() >= self.max_turns {
+            return 0_f64;
+        }
+
+        let player = self.config.player_turn(query_answer.len());
+        let view = cards_from_player(distr, player);
+        let key = info_key(&view, query_answer);
+        let node = match self.nodes.get(&key) {
+            Some(node) => node,
+            None => {
+                let actions = reasonable_moves(&self.config, query_answer, &view);
+                self.nodes
+                    .entry(key.clone())
+                    .or_insert(InfoNode::new(actions))
+            }
+        };
+        if node.actions.is_empty() {
+            return 0_f64;
+        }
+        let actions = node.actions.clone();
+        let strategy = node.current_strategy();
+
+        if player == traverser {
+            let mut utils = vec![0_f64; actions.len()];
+            for (i, m) in actions.iter().enumerate() {
+                query_answer.push(answer(&self.config, distr, m.clone(), player));
+                utils[i] = self.traverse(distr, query_answer, traverser);
+                query_answer.pop();
+            }
+            let node_util: f64 = strategy.iter().zip(&utils).map(|(s, u)| s * u).sum();
+            let node = self.nodes.get_mut(&key).unwrap();
+            for (regret, u) in node.regret.iter_mut().zip(&utils) {
+                *regret += u - node_util;
+                if self.variant == CfrVariant::Plus {
+                    *regret = regret.max(0_f64);
+                }
+            }
+            node_util
+        } else {
+            let weight = match self.variant {
+                CfrVariant::Vanilla => 1_f64,
+                CfrVariant::Plus => self.iteration as f64,
+            };
+            let node = self.nodes.get_mut(&key).unwrap();
+            for (sum, s) in node.strategy_sum.iter_mut().zip(&strategy) {
+                *sum += weight * s;
+            }
+            let i = sample_index(&mut self.rng, &strategy);
+            query_answer.push(answer(&self.config, distr, actions[i].clone(), player));
+            let u = self.traverse(distr, query_answer, traverser);
+            query_answer.pop();
+            u
+        }
+    }
+
+    pub fn average_policy(&self) -> CfrPolicy {
+        // 平均戦略が一度も積まれていない情報集合は一様分布なので、保存しない（CfrPlayer 側でランダムに打つ）
+        let table = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.strategy_sum.iter().any(|s| *s > 0_f64))
+            .map(|(key, node)| {
+                let dist = node
+                    .actions
+                    .iter()
+                    .cloned()
+                    .zip(node.average_strategy())
+                    .collect();
+                (key.clone(), dist)
+            })
+            .collect();
+        CfrPolicy {
+            config: self.config.clone(),
+            max_turns: self.max_turns,
+            variant: self.variant,
+            iterations: self.iteration,
+            table,
+        }
+    }
+}
+
+fn sample_index<R: Rng>(rng: &mut R, probs: &[f64]) -> usize {
+    let mut x: f64 = rng.gen();
+    for (i, p) in probs.iter().enumerate() {
+        if x < *p {
+            return i;
+        }
+        x -= p;
+    }
+    probs.len() - 1
+}
+
+// 情報集合ごとの平均戦略。ファイルには JSON で保存する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CfrPolicy {
+    pub config: GameConfig,
+    pub max_turns: usize,
+    pub variant: CfrVariant,
+    pub iterations: usize,
+    pub table: HashMap<String, Vec<(Move, f64)>>,
+}
+
+impl CfrPolicy {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self).map_err(io::Error::other)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(io::Error::other)
+    }
+
+    pub fn distribution(&self, info: &Info) -> Option<&Vec<(Move, f64)>> {
+        if info.config != self.config {
+            return None;
+        }
+        self.table.get(&info_key(&info.view, &info.query_answer))
+    }
+}
+
+// 保存された平均戦略に従って打つ。
+// 学習で到達しなかった情報集合では、確定していれば宣言し、そうでなければ可能な手からランダムに打つ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfrPlayer<R>
+where
+    R: Rng,
+{
+    policy: CfrPolicy,
+    rng: R,
+}
+
+impl<R> CfrPlayer<R>
+where
+    R: Rng,
+{
+    pub fn new(policy: CfrPolicy, rng: R) -> Self {
+        Self { policy, rng }
+    }
+}
+
+impl CfrPlayer<SmallRng> {
+    // 同じ seed なら同じ手を打つ。registry からは BuildContext の seed が渡る
+    pub fn from_policy(policy: CfrPolicy, seed: u64) -> Self {
+        Self::new(policy, SmallRng::seed_from_u64(seed))
+    }
+}
+
+impl<R> Agent for CfrPlayer<R>
+where
+    R: Rng + Send,
+{
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        if let Some(dist) = self.policy.distribution(&info) {
+            let probs: Vec<f64> = dist.iter().map(|(_, p)| *p).collect();
+            let m = &dist[sample_index(&mut self.rng, &probs)].0;
+            if possible_moves.contains(m) {
+                return m.clone();
+            }
+        }
+        if let Some(answer) = answerable_info(&info) {
+            return answer;
+        }
+        let actions = reasonable_moves(&info.config, &info.query_answer, &info.view);
+        random_vec(&mut self.rng, actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use game_core::{config::three_small, utils::auto_game};
+
+    use super::*;
+
+    #[test]
+    fn cfr_policy_round_trips_and_plays() {
+        let config = three_small();
+        let mut solver = CfrSolver::new(config.clone(), 6, CfrVariant::Plus, 0);
+        solver.run(20);
+        let policy = solver.average_policy();
+        assert!(!policy.table.is_empty());
+        for dist in policy.table.values() {
+            let sum: f64 = dist.iter().map(|(_, p)| p).sum();
+            assert!((sum - 1_f64).abs() < 1e-9);
+        }
+
+        // 並列に走るテストとぶつからないように、毎回違う名前にする
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!(
+            "game_ai_cfr_test_policy_{}_{nanos}.json",
+            std::process::id()
+        ));
+        policy.save(&path).unwrap();
+        let loaded = CfrPolicy::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.table.len(), policy.table.len());
+
+        let agents: Vec<Box<dyn Agent<Game = Game>>> = (0..config.player_num())
+            .map(|player| {
+                Box::new(CfrPlayer::from_policy(loaded.clone(), player as u64))
+                    as Box<dyn Agent<Game = Game>>
+            })
+            .collect();
+        let winner = auto_game(config.gen_random(5), agents);
+        assert_eq!(winner.iter().sum::<usize>(), 1);
+    }
+}
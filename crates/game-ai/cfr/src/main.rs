@@ -0,0 +1,111 @@
+use game_ai_cfr::{CfrSolver, CfrVariant};
+use game_core::config;
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(message) => usage_and_exit(&message),
+    };
+
+    let config = config::by_name(&args.config)
+        .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {}", args.config)));
+    let mut solver = CfrSolver::new(config, args.max_turns, args.variant, args.seed);
+
+    let mut done = 0;
+    while done < args.iterations {
+        let step = args.report.min(args.iterations - done);
+        solver.run(step);
+        done += step;
+        eprintln!("iterations: {done} info_sets: {}", solver.info_set_num());
+    }
+
+    solver
+        .average_policy()
+        .save(&args.out)
+        .expect("failed to save policy");
+    eprintln!("saved policy to {}", args.out);
+}
+
+struct Args {
+    config: String,
+    iterations: usize,
+    max_turns: usize,
+    variant: CfrVariant,
+    seed: u64,
+    report: usize,
+    out: String,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        config: "three_small".to_string(),
+        iterations: 1000,
+        max_turns: 9,
+        variant: CfrVariant::Plus,
+        seed: 0,
+        report: 100,
+        out: "cfr_policy.json".to_string(),
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--config" => {
+                i += 1;
+                parsed.config = parse_string(args.get(i), "--config")?;
+            }
+            "--iterations" => {
+                i += 1;
+                parsed.iterations = parse_usize(args.get(i), "--iterations")?;
+            }
+            "--max-turns" => {
+                i += 1;
+                parsed.max_turns = parse_usize(args.get(i), "--max-turns")?;
+            }
+            "--variant" => {
+                i += 1;
+                parsed.variant = match parse_string(args.get(i), "--variant")?.as_str() {
+                    "vanilla" => CfrVariant::Vanilla,
+                    "plus" => CfrVariant::Plus,
+                    variant => return Err(format!("unknown variant: {variant}")),
+                };
+            }
+            "--seed" => {
+                i += 1;
+                parsed.seed = parse_usize(args.get(i), "--seed")? as u64;
+            }
+            "--report" => {
+                i += 1;
+                parsed.report = parse_usize(args.get(i), "--report")?.max(1);
+            }
+            "--out" => {
+                i += 1;
+                parsed.out = parse_string(args.get(i), "--out")?;
+            }
+            flag => return Err(format!("unknown argument: {flag}")),
+        }
+        i += 1;
+    }
+
+    Ok(parsed)
+}
+
+fn parse_string(value: Option<&String>, flag: &str) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("missing value for {flag}"))
+}
+
+fn parse_usize(value: Option<&String>, flag: &str) -> Result<usize, String> {
+    value
+        .ok_or_else(|| format!("missing value for {flag}"))?
+        .parse::<usize>()
+        .map_err(|_| format!("invalid integer for {flag}"))
+}
+
+fn usage_and_exit(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: game-ai-cfr [--config NAME] [--iterations N] [--max-turns N] [--variant vanilla|plus] [--seed N] [--report N] [--out PATH]");
+    eprintln!("example: cargo run --release -p game-ai-cfr -- --config three_small --iterations 2000 --out cfr_policy.json");
+    std::process::exit(2);
+}
@@ -0,0 +1,170 @@
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame},
+    defs::*,
+    utils::*,
+};
+use itertools::Itertools;
+
+// 本当の配置を知っている AI。他の AI がどれだけ情報を取りこぼしているかを測るための上限として使う。
+// 配置は Info からではなく with_oracle で渡すので、ゲームごとに作り直すこと。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheaterMode {
+    // 最初の手番ですぐに本当の頭を宣言する
+    Declare,
+    // 本当の答えを使って、自分の頭が確定する一番短い query の列を探し、その先頭を打つ。
+    // 確定したら宣言する。
+    Solve,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheaterPlayer {
+    distr: Distr,
+    mode: CheaterMode,
+    // Solve で探す query の列の長さの上限。これで確定しなければ宣言する。
+    max_depth: usize,
+}
+
+impl CheaterPlayer {
+    pub const DEFAULT_MAX_DEPTH: usize = 6;
+
+    pub fn with_oracle(distr: Distr, mode: CheaterMode) -> Self {
+        Self {
+            distr,
+            mode,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+// 本当の配置 truth のもとで、worlds を queries の答えで絞り込んだあと、player の頭が確定するか
+fn determined(
+    config: &GameConfig,
+    worlds: &[Distr],
+    truth: &Distr,
+    queries: &[&Move],
+    player: usize,
+) -> bool {
+    let mut heads = worlds
+        .iter()
+        .filter(|distr| {
+            queries.iter().all(|q| {
+                answer(config, distr, (*q).clone(), player)
+                    == answer(config, truth, (*q).clone(), player)
+            })
+        })
+        .map(|distr| players_head(distr, player));
+    let Some(head) = heads.next() else {
+        return false;
+    };
+    heads.all(|other| other == head)
+}
+
+// 自分の頭が確定する一番短い query の列。もう確定していれば空。max_depth 以内で見つからなければ None。
+pub fn shortest_solving_queries(info: &Info, truth: &Distr, max_depth: usize) -> Option<Vec<Move>> {
+    let player = info.player_turn();
+    let worlds: Vec<Distr> =
+        possible_states(&info.config, &info.query_answer, &info.view).collect();
+    let queries: Vec<Move> = movable_query_ref(&info.config, &info.query_answer, player).collect();
+    // 順番は答えに関係しないので組み合わせだけ見ればよい
+    (0..=max_depth.min(queries.len())).find_map(|depth| {
+        queries
+            .iter()
+            .combinations(depth)
+            .find(|chosen| determined(&info.config, &worlds, truth, chosen, player))
+            .map(|chosen| chosen.into_iter().cloned().collect())
+    })
+}
+
+impl Agent for CheaterPlayer {
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        let declare = Move::Declare {
+            declare: players_head(&self.distr, info.player_turn()).clone(),
+        };
+        if self.mode == CheaterMode::Declare {
+            return declare;
+        }
+        match shortest_solving_queries(&info, &self.distr, self.max_depth) {
+            Some(queries) if !queries.is_empty() => queries.into_iter().next().unwrap(),
+            _ => declare,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::config::three_small;
+
+    use super::*;
+
+    fn right_declare(game: &Game) -> bool {
+        matches!(
+            game.history().last(),
+            Some(MoveAns::Declare { ans: true, .. })
+        )
+    }
+
+    #[test]
+    fn declare_mode_declares_the_true_head_at_once() {
+        let config = three_small();
+        for seed in 0..20 {
+            let mut game = config.gen_random(seed);
+            let (info, possible_moves) = game.info_and_move_now();
+            let mut cheater = CheaterPlayer::with_oracle(game.distr(), CheaterMode::Declare);
+            let m = cheater.use_info(info, possible_moves);
+            assert!(game.move_game(m));
+            assert!(right_declare(&game));
+        }
+    }
+
+    #[test]
+    fn solve_mode_queries_along_the_shortest_solution() {
+        let config = three_small();
+        for seed in 0..20 {
+            let mut game = config.gen_random(seed);
+            let mut agents: Vec<_> = (0..config.player_num())
+                .map(|_| CheaterPlayer::with_oracle(game.distr(), CheaterMode::Solve))
+                .collect();
+            // 各席の、最初の手番で求めた一番短い列の長さと、その後に打った query の数と、前の手番の残りの長さ
+            let mut first = vec![None; config.player_num()];
+            let mut queried = vec![0; config.player_num()];
+            let mut last: Vec<Option<usize>> = vec![None; config.player_num()];
+            while game.is_win().is_none() {
+                let player = game.player_turn();
+                let (info, possible_moves) = game.info_and_move_now();
+                let rest = shortest_solving_queries(
+                    &info,
+                    &game.distr(),
+                    CheaterPlayer::DEFAULT_MAX_DEPTH,
+                )
+                .unwrap()
+                .len();
+                let first = *first[player].get_or_insert(rest);
+                // 自分の query で一つ、ほかの人の答えでさらに縮むことはあっても、伸びることはない
+                if let Some(last) = last[player] {
+                    assert!(rest < last);
+                }
+                last[player] = Some(rest);
+                let m = agents[player].use_info(info, possible_moves);
+                assert!(game.move_game(m.clone()));
+                match m {
+                    Move::Query { .. } => {
+                        assert!(rest > 0);
+                        queried[player] += 1;
+                        assert!(queried[player] <= first);
+                    }
+                    Move::Declare { .. } => {
+                        // 確定してから宣言するので外さない
+                        assert_eq!(rest, 0);
+                        assert!(right_declare(&game));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
+use game_artifact::{book_rationale, ArtifactStore, BookCache};
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, Candidate, Decision, ImperfectInfoGame, PolicyAgent, Rationale},
     defs::*,
     utils::*,
 };
@@ -9,18 +10,35 @@ use game_core::{
 // 現在の履歴から可能な状態の全体を考え、各 query に対して可能な状態の回答の分布のエントロピーを計算する。
 // 一番エントロピーが低いと、情報量がより得られているので、その手を選ぶ。
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct UseEntropyPlayer;
+pub struct UseEntropyPlayer {
+    last: Option<Rationale<Move>>,
+    book: Option<BookCache>,
+}
 
-pub fn entoropy(info: Info) -> Option<Move> {
-    let who = info.player_turn();
-    let distrs: Vec<_> = possible_states(&info.config, &info.query_answer, &info.view).collect();
+impl UseEntropyPlayer {
+    // 序盤の本の名前。手の選び方を変えたら BOOK_VERSION を上げる
+    pub const BOOK_AGENT: &'static str = "entropy";
+    pub const BOOK_VERSION: u32 = 1;
+
+    // store に本があれば、序盤はそれを引く
+    pub fn with_book(store: ArtifactStore) -> Self {
+        Self {
+            last: None,
+            book: Some(BookCache::new(store, Self::BOOK_AGENT, Self::BOOK_VERSION)),
+        }
+    }
+}
 
+// 各 query の Σ (i/N) log2(i)（i は回答ごとの状態の数）。小さいほど質問のあとに残る状態が少ない。
+// 回答が 1 通りしかない query は何もわからないので除く。
+pub fn query_scores(info: &Info, distrs: &[Distr]) -> Vec<(f64, Move)> {
+    let who = info.player_turn();
     info.movable_query()
         .into_iter()
         .filter_map(|q| {
             let mut distribution = vec![0; info.config.cards_num()];
 
-            for distr in &distrs {
+            for distr in distrs {
                 let MoveAns::Query {
                     who: _,
                     query_to: _,
@@ -49,8 +67,79 @@ pub fn entoropy(info: Info) -> Option<Move> {
                 None
             }
         })
+        .collect()
+}
+
+pub fn entoropy(info: Info) -> Option<Move> {
+    let distrs: Vec<_> = possible_states(&info.config, &info.query_answer, &info.view).collect();
+    best_query(query_scores(&info, &distrs))
+}
+
+pub fn best_query(scores: Vec<(f64, Move)>) -> Option<Move> {
+    scores
+        .into_iter()
         .min_by(|(entropy1, _), (entropy2, _)| entropy1.partial_cmp(entropy2).unwrap())
-        .map(|(_, m)| m.clone())
+        .map(|(_, m)| m)
+}
+
+// まだ宣言していない頭ごとの、可能な状態の中での割合。多い順。
+pub fn head_frequencies(info: &Info, distrs: &[Distr]) -> Vec<(Move, f64)> {
+    let who = info.player_turn();
+    let possible_declare = info.movable_declare();
+    let mut maps: HashMap<Move, usize> = HashMap::new();
+    for distr in distrs {
+        let head = Move::Declare {
+            declare: players_head(distr, who).clone(),
+        };
+        if !possible_declare.contains(&head) {
+            continue;
+        }
+        *maps.entry(head).or_default() += 1;
+    }
+    let mut heads: Vec<_> = maps
+        .into_iter()
+        .map(|(m, n)| (m, n as f64 / distrs.len() as f64))
+        .collect();
+    heads.sort_by(|(m1, p1), (m2, p2)| p2.partial_cmp(p1).unwrap().then(m1.cmp(m2)));
+    heads
+}
+
+// 質問で情報が得られないときに宣言する、一番ありそうな頭
+fn most_likely_declare(info: &Info, distrs: &[Distr]) -> Move {
+    head_frequencies(info, distrs).into_iter().next().unwrap().0
+}
+
+pub fn entropy_rationale(
+    worlds: usize,
+    scores: &[(f64, Move)],
+    heads: &[(Move, f64)],
+) -> Rationale<Move> {
+    let queries = scores.iter().map(|(entropy, q)| Candidate {
+        kind: "entropy".to_string(),
+        action: q.clone(),
+        value: *entropy,
+    });
+    let heads = heads.iter().map(|(m, p)| Candidate {
+        kind: "head_frequency".to_string(),
+        action: m.clone(),
+        value: *p,
+    });
+    Rationale {
+        worlds: Some(worlds),
+        candidates: queries.chain(heads).collect(),
+        decision: None,
+    }
+}
+
+// 頭が確定しているときの理由
+pub fn certain_rationale() -> Rationale<Move> {
+    Rationale {
+        decision: Some(Decision {
+            declare: true,
+            values: vec![("p".to_string(), 1_f64)],
+        }),
+        ..Default::default()
+    }
 }
 
 impl Agent for UseEntropyPlayer {
@@ -59,70 +148,128 @@ impl Agent for UseEntropyPlayer {
     fn use_info(
         &mut self,
         info: <Self::Game as ImperfectInfoGame>::Info,
-        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
     ) -> <Self::Game as ImperfectInfoGame>::Move {
         if let Some(answer) = answerable_info(&info) {
+            self.last = Some(certain_rationale());
             return answer;
         }
+        if let Some(m) = self.book.as_mut().and_then(|book| book.lookup(&info)) {
+            if possible_moves.contains(&m) {
+                self.last = Some(book_rationale(&m));
+                return m;
+            }
+        }
 
-        let who = info.player_turn();
         let distrs: Vec<_> =
             possible_states(&info.config, &info.query_answer, &info.view).collect();
 
         debug_assert!(!distrs.is_empty());
 
-        if let Some((_, q)) = info
-            .movable_query()
+        let scores = query_scores(&info, &distrs);
+        let heads = head_frequencies(&info, &distrs);
+        self.last = Some(entropy_rationale(distrs.len(), &scores, &heads));
+        match best_query(scores) {
+            Some(q) => q,
+            None => heads.into_iter().next().unwrap().0,
+        }
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
+    }
+}
+
+// UseEntropyPlayer の混合戦略版。query の値 s に対して exp(-s / temperature) に比例する確率で選ぶ。
+// temperature が 0 なら UseEntropyPlayer と同じ手を確率 1 で選ぶ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftmaxEntropyPlayer {
+    pub temperature: f64,
+}
+
+impl SoftmaxEntropyPlayer {
+    pub fn new(temperature: f64) -> Self {
+        assert!(temperature >= 0_f64);
+        Self { temperature }
+    }
+}
+
+impl Default for SoftmaxEntropyPlayer {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl PolicyAgent for SoftmaxEntropyPlayer {
+    type Game = Game;
+
+    fn policy(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> Vec<(<Self::Game as ImperfectInfoGame>::Move, f64)> {
+        if let Some(answer) = answerable_info(&info) {
+            return vec![(answer, 1_f64)];
+        }
+
+        let distrs: Vec<_> =
+            possible_states(&info.config, &info.query_answer, &info.view).collect();
+        let scores = query_scores(&info, &distrs);
+        if scores.is_empty() {
+            return vec![(most_likely_declare(&info, &distrs), 1_f64)];
+        }
+        if self.temperature == 0_f64 {
+            return vec![(best_query(scores).unwrap(), 1_f64)];
+        }
+
+        let min = scores.iter().map(|(s, _)| *s).fold(f64::INFINITY, f64::min);
+        let weights: Vec<f64> = scores
+            .iter()
+            .map(|(s, _)| (-(s - min) / self.temperature).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        scores
             .into_iter()
-            .filter_map(|q| {
-                let mut distribution = vec![0; info.config.cards_num()];
-
-                for distr in &distrs {
-                    let MoveAns::Query {
-                        who: _,
-                        query_to: _,
-                        query_sort: _,
-                        ans,
-                    } = answer(&info.config, distr, q.clone(), who)
-                    else {
-                        unreachable!()
-                    };
-                    distribution[ans] += 1;
-                }
+            .zip(weights)
+            .map(|((_, m), w)| (m, w / total))
+            .collect()
+    }
+}
 
-                let mut k = 0;
-                let mut entropy: f64 = 0_f64;
-                for i in distribution {
-                    if i == 0 {
-                        continue;
-                    }
-                    k += 1;
-                    entropy += ((i as f64) / (distrs.len() as f64)) * (i as f64).log2();
-                }
+#[cfg(test)]
+mod tests {
+    use game_core::{abstract_game::Sampling, config::three_small, utils::auto_game};
 
-                if k > 1 {
-                    Some((entropy, q))
-                } else {
-                    None
-                }
+    use super::*;
+
+    #[test]
+    fn softmax_policy_is_a_distribution() {
+        let config = three_small();
+        let game = config.gen_random(0);
+        let (info, moves) = game.info_and_move_now();
+
+        let cold = SoftmaxEntropyPlayer::new(0_f64).policy(info.clone(), moves.clone());
+        assert_eq!(
+            cold,
+            vec![(
+                UseEntropyPlayer::default().use_info(info.clone(), moves.clone()),
+                1_f64
+            )]
+        );
+
+        let warm = SoftmaxEntropyPlayer::default().policy(info, moves);
+        let total: f64 = warm.iter().map(|(_, p)| p).sum();
+        assert!((total - 1_f64).abs() < 1e-9);
+
+        let agents: Vec<Box<dyn Agent<Game = Game>>> = (0..config.player_num())
+            .map(|player| {
+                Box::new(Sampling::seeded(
+                    SoftmaxEntropyPlayer::default(),
+                    player as u64,
+                )) as Box<dyn Agent<Game = Game>>
             })
-            .min_by(|(entropy1, _), (entropy2, _)| entropy1.partial_cmp(entropy2).unwrap())
-        {
-            q
-        } else {
-            let possible_declare = info.movable_declare();
-            let mut maps: HashMap<Move, usize> = HashMap::new();
-            for distr in distrs {
-                let head = Move::Declare {
-                    declare: players_head(&distr, who).clone(),
-                };
-                if !possible_declare.contains(&head) {
-                    continue;
-                }
-                let entry = maps.entry(head);
-                entry.and_modify(|i| *i += 1).or_default();
-            }
-            maps.into_iter().max_by_key(|(_, n)| *n).unwrap().0
-        }
+            .collect();
+        let winner = auto_game(config.gen_random(1), agents);
+        assert_eq!(winner.iter().sum::<usize>(), 1);
     }
 }
@@ -0,0 +1,342 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame, Player},
+    defs::*,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+// 別のプロセスを AI として使う。ほかの言語で書いた AI を試すためのもの。
+// 標準入力に 1 行 1 つの JSON（Request）を書き、標準出力から 1 行 1 つの JSON を読む。標準エラーはそのまま流す。
+// - 立ち上げたら hello を送り、handshake_timeout 以内に Ready が返ってこなければ失敗
+// - 手番では turn を送り、move_timeout 以内に Reply が返ってこなければ失敗
+// - game_start / observe / game_end / quit には返事をしない
+// 失敗したらプロセスを止めて use_info は panic する（Runner が Fault として扱う）。
+// 次に手を聞くときにプロセスを立ち上げ直し、今のゲームの game_start から送り直す。
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request<'a> {
+    Hello {
+        protocol: u32,
+    },
+    GameStart {
+        config: &'a GameConfig,
+        player: Player,
+    },
+    // game-cli の StateResponse と同じ形（history の文字列はない）
+    Turn {
+        you: Player,
+        current_turn: Player,
+        your_turn: bool,
+        winner: Option<Vec<usize>>,
+        info: &'a Info,
+        possible_moves: &'a [Move],
+    },
+    Observe {
+        move_ans: &'a MoveAns,
+    },
+    GameEnd {
+        winner: &'a [usize],
+    },
+    Quit,
+}
+
+// hello への返事
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ready {
+    pub protocol: u32,
+    #[serde(default)]
+    pub name: String,
+}
+
+// turn への返事。game-cli の MoveRequest と同じ形
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub action: Move,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalOptions {
+    pub handshake_timeout: Duration,
+    pub move_timeout: Duration,
+}
+
+impl Default for ExternalOptions {
+    fn default() -> Self {
+        Self {
+            handshake_timeout: Duration::from_secs(10),
+            move_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    // 標準出力の行。読むのは別スレッドで、時間切れを見るために channel で受け取る
+    lines: Receiver<String>,
+}
+
+impl Process {
+    fn spawn(command: &[String]) -> io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    fn send(&mut self, request: &Request) -> io::Result<()> {
+        serde_json::to_writer(&mut self.stdin, request)?;
+        self.stdin.write_all(b"\n")?;
+        self.stdin.flush()
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self, timeout: Duration) -> io::Result<T> {
+        let line = match self.lines.recv_timeout(timeout) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no reply in {timeout:?}"),
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "process exited",
+                ))
+            }
+        };
+        serde_json::from_str(&line)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{err}: {line}")))
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.send(&Request::Quit);
+        // 少し待っても終わらなければ止める
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub struct ExternalPlayer {
+    command: Vec<String>,
+    options: ExternalOptions,
+    name: String,
+    process: Option<Process>,
+    // 立ち上げ直したときに送り直す
+    game: Option<(GameConfig, Player)>,
+}
+
+impl ExternalPlayer {
+    // command[0] がプログラム、残りが引数。立ち上げて handshake まで済ませる
+    pub fn spawn(command: Vec<String>, options: ExternalOptions) -> io::Result<Self> {
+        let mut player = Self {
+            command,
+            options,
+            name: String::new(),
+            process: None,
+            game: None,
+        };
+        player.process()?;
+        Ok(player)
+    }
+
+    // Ready で名乗った名前
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 動いていなければ立ち上げて hello を送る
+    fn process(&mut self) -> io::Result<&mut Process> {
+        if self.process.is_none() {
+            let mut process = Process::spawn(&self.command)?;
+            process.send(&Request::Hello {
+                protocol: PROTOCOL_VERSION,
+            })?;
+            let ready: Ready = process.receive(self.options.handshake_timeout)?;
+            if ready.protocol != PROTOCOL_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "protocol {} is not supported (expected {PROTOCOL_VERSION})",
+                        ready.protocol
+                    ),
+                ));
+            }
+            if let Some((config, player)) = &self.game {
+                process.send(&Request::GameStart {
+                    config,
+                    player: *player,
+                })?;
+            }
+            self.name = ready.name;
+            self.process = Some(process);
+        }
+        Ok(self.process.as_mut().unwrap())
+    }
+
+    // 返事のいらないものを送る。失敗したらプロセスを捨て、次に手を聞くときに立ち上げ直す
+    fn notify(&mut self, request: &Request) {
+        if let Some(process) = &mut self.process {
+            if process.send(request).is_err() {
+                self.process = None;
+            }
+        }
+    }
+
+    fn ask(&mut self, request: &Request) -> io::Result<Move> {
+        let timeout = self.options.move_timeout;
+        let result = self.process().and_then(|process| {
+            process.send(request)?;
+            process.receive::<Reply>(timeout)
+        });
+        if result.is_err() {
+            self.process = None;
+        }
+        result.map(|reply| reply.action)
+    }
+}
+
+impl Agent for ExternalPlayer {
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        let current_turn = info.player_turn();
+        let you = self.game.as_ref().map_or(current_turn, |(_, seat)| *seat);
+        let request = Request::Turn {
+            you,
+            current_turn,
+            your_turn: true,
+            winner: None,
+            info: &info,
+            possible_moves: &possible_moves,
+        };
+        match self.ask(&request) {
+            Ok(m) => m,
+            Err(err) => panic!("external agent {:?}: {err}", self.command),
+        }
+    }
+
+    fn on_game_start(&mut self, config: &GameConfig, seat: Player) {
+        self.game = Some((config.clone(), seat));
+        self.notify(&Request::GameStart {
+            config,
+            player: seat,
+        });
+    }
+
+    fn observe(&mut self, move_ans: &MoveAns) {
+        self.notify(&Request::Observe { move_ans });
+    }
+
+    fn on_game_end(&mut self, outcome: &[usize]) {
+        self.game = None;
+        self.notify(&Request::GameEnd { winner: outcome });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use game_core::config::three_small;
+
+    use super::*;
+
+    // 最初の合法手を返すだけの AI
+    const FIRST_MOVE: &str = r#"
+        while read -r line; do
+            case "$line" in
+                *'"type":"hello"'*) echo '{"protocol":1,"name":"first"}' ;;
+                *'"type":"turn"'*) echo "$line" | sed 's/.*"possible_moves":\[\({"[A-Za-z]*":{[^}]*}}\).*/{"action":\1}/' ;;
+                *'"type":"quit"'*) exit 0 ;;
+            esac
+        done
+    "#;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_process_answers_and_failures_are_reported() {
+        let config = three_small();
+        let mut game = config.gen_random(0);
+        let mut player = ExternalPlayer::spawn(sh(FIRST_MOVE), ExternalOptions::default()).unwrap();
+        assert_eq!(player.name(), "first");
+        player.on_game_start(&config, 0);
+        let (info, moves) = game.info_and_move_now();
+        let m = player.use_info(info, moves.clone());
+        assert_eq!(m, moves[0]);
+        game.move_game(m);
+        player.observe(&game.history().pop().unwrap());
+
+        // 返事がなければ時間切れ
+        let options = ExternalOptions {
+            handshake_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let err = ExternalPlayer::spawn(sh("sleep 5"), options.clone())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // 手番で落ちたら panic し、次は立ち上げ直す
+        let crash = r#"read -r line; echo '{"protocol":1}'; read -r line; exit 1"#;
+        let mut player = ExternalPlayer::spawn(sh(crash), options).unwrap();
+        let (info, moves) = config.gen_random(1).info_and_move_now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            player.use_info(info.clone(), moves.clone())
+        }));
+        assert!(result.is_err());
+        assert!(player.process.is_none());
+        player.on_game_start(&config, 0);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| player.use_info(info, moves))).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame, Player},
+    defs::*,
+    utils::*,
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+// Single-Observer ISMCTS
+// 自分から見て可能な配置を一つ選んで（determinize）、その配置の上で UCT を 1 回まわす、を繰り返す。
+// 木のノードは手の列で表し、回答は determinize した配置によって変わる。
+// 各ノードでは手番のプレイヤーが自分の勝率を最大化するように選ぶ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsmctsConfig {
+    pub iterations: usize,
+    // wasm では Instant が使えないので None にしておくこと
+    pub time_limit: Option<Duration>,
+    pub exploration: f64,
+    // rollout でこの手数以内に決着がつかなければ全員 0 とする
+    pub rollout_depth: usize,
+}
+
+impl Default for IsmctsConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 200,
+            time_limit: None,
+            exploration: std::f64::consts::SQRT_2,
+            rollout_depth: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsmctsPlayer<R>
+where
+    R: Rng,
+{
+    rng: R,
+    config: IsmctsConfig,
+}
+
+impl<R> IsmctsPlayer<R>
+where
+    R: Rng,
+{
+    pub fn new(rng: R, config: IsmctsConfig) -> Self {
+        Self { rng, config }
+    }
+}
+
+impl IsmctsPlayer<SmallRng> {
+    // 同じ seed なら同じ手を打つ。registry からは BuildContext の seed が渡る
+    pub fn seeded(seed: u64, config: IsmctsConfig) -> Self {
+        Self::new(SmallRng::seed_from_u64(seed), config)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    visits: usize,
+    // 兄弟の中でこのノードが選択可能だった回数
+    avail: usize,
+    reward: Vec<f64>,
+    children: Vec<(Move, usize)>,
+}
+
+impl Node {
+    fn new(player_num: usize) -> Self {
+        Self {
+            visits: 0,
+            avail: 0,
+            reward: vec![0_f64; player_num],
+            children: vec![],
+        }
+    }
+}
+
+// determinize した 1 つの配置。各プレイヤーから見て可能な配置は、最初に要るときに info の履歴まで
+// 一度だけ列挙し、あとはシミュレーションで増えた query の答えで絞り込む。
+// （手ごとに possible_states を列挙し直すと、iteration がとても重くなる）
+struct Determinization<'a> {
+    config: &'a GameConfig,
+    root: &'a [MoveAns],
+    world: &'a Distr,
+    // プレイヤーごとの (まだ残っている配置, 絞り込みに使った履歴の長さ)
+    seen: Vec<Option<(Vec<Distr>, usize)>>,
+}
+
+impl<'a> Determinization<'a> {
+    fn new(info: &'a Info, world: &'a Distr) -> Self {
+        Self {
+            config: &info.config,
+            root: &info.query_answer,
+            world,
+            seen: vec![None; info.config.player_num()],
+        }
+    }
+
+    // answerable と同じ。player の view から頭が確定するなら、その宣言
+    fn answerable(&mut self, query_answer: &[MoveAns], player: Player) -> Option<Move> {
+        let (config, world) = (self.config, self.world);
+        let (states, seen) = self.seen[player].get_or_insert_with(|| {
+            let view = cards_from_player(world, player);
+            let states = possible_states_of(config, self.root, &view, player).collect();
+            (states, self.root.len())
+        });
+        for qa in &query_answer[*seen..] {
+            // シミュレーションでは確定した宣言しかしないので、宣言が来たらそこで決着している
+            let MoveAns::Query { who, .. } = qa else {
+                continue;
+            };
+            let m = qa.move_of_this();
+            states.retain(|distr| answer(config, distr, m.clone(), *who) == *qa);
+        }
+        *seen = query_answer.len();
+        let head = players_head(world, player);
+        states
+            .iter()
+            .all(|distr| players_head(distr, player) == head)
+            .then(|| Move::Declare {
+                declare: head.clone(),
+            })
+    }
+
+    // 手番のプレイヤーが world の上でとれる手。
+    // 自分の view から頭が確定するなら宣言だけ、そうでなければ質問のみを考える。
+    fn candidate_moves(&mut self, query_answer: &[MoveAns], player: Player) -> Vec<Move> {
+        if let Some(declare) = self.answerable(query_answer, player) {
+            return vec![declare];
+        }
+        movable_query_ref(self.config, query_answer, player).collect()
+    }
+}
+
+fn winner(config: &GameConfig, query_answer: &[MoveAns]) -> Option<Vec<f64>> {
+    if let Some(MoveAns::Declare { who, ans: true, .. }) = query_answer.last() {
+        let mut v = vec![0_f64; config.player_num()];
+        v[*who] = 1_f64;
+        Some(v)
+    } else {
+        None
+    }
+}
+
+pub fn ismcts<R>(info: &Info, config: &IsmctsConfig, rng: &mut R) -> Option<(Move, Vec<f64>)>
+where
+    R: Rng,
+{
+    let game_config = &info.config;
+    let player_num = game_config.player_num();
+    let worlds: Vec<Distr> = possible_states(game_config, &info.query_answer, &info.view).collect();
+    if worlds.is_empty() {
+        return None;
+    }
+
+    let started = config.time_limit.map(|limit| (Instant::now(), limit));
+    let mut tree = vec![Node::new(player_num)];
+
+    for _ in 0..config.iterations {
+        if let Some((started, limit)) = started {
+            if started.elapsed() >= limit {
+                break;
+            }
+        }
+
+        let world = &worlds[rng.gen_range(0..worlds.len())];
+        let mut determinization = Determinization::new(info, world);
+        let mut query_answer = info.query_answer.clone();
+        let mut path = vec![0];
+        let mut node = 0;
+
+        // selection と expansion
+        loop {
+            if winner(game_config, &query_answer).is_some() {
+                break;
+            }
+            let player = game_config.player_turn(query_answer.len());
+            let moves = determinization.candidate_moves(&query_answer, player);
+            if moves.is_empty() {
+                break;
+            }
+
+            let mut untried = vec![];
+            let mut available = vec![];
+            for m in moves {
+                match tree[node].children.iter().find(|(c, _)| *c == m) {
+                    Some((_, child)) => available.push(*child),
+                    None => untried.push(m),
+                }
+            }
+            for child in &available {
+                tree[*child].avail += 1;
+            }
+
+            let (m, next) = if !untried.is_empty() {
+                let m = random_vec(rng, untried);
+                let child = tree.len();
+                let mut new_node = Node::new(player_num);
+                new_node.avail = 1;
+                tree.push(new_node);
+                tree[node].children.push((m.clone(), child));
+                (m, child)
+            } else {
+                let child = available
+                    .into_iter()
+                    .max_by(|a, b| {
+                        let ua = ucb(&tree[*a], player, config.exploration);
+                        let ub = ucb(&tree[*b], player, config.exploration);
+                        ua.partial_cmp(&ub).unwrap()
+                    })
+                    .unwrap();
+                let m = tree[node]
+                    .children
+                    .iter()
+                    .find(|(_, c)| *c == child)
+                    .unwrap()
+                    .0
+                    .clone();
+                (m, child)
+            };
+
+            query_answer.push(answer(game_config, world, m, player));
+            path.push(next);
+            let expanded = tree[next].visits == 0;
+            node = next;
+            if expanded {
+                break;
+            }
+        }
+
+        // rollout
+        for _ in 0..config.rollout_depth {
+            if winner(game_config, &query_answer).is_some() {
+                break;
+            }
+            let player = game_config.player_turn(query_answer.len());
+            let moves = determinization.candidate_moves(&query_answer, player);
+            if moves.is_empty() {
+                break;
+            }
+            let m = random_vec(rng, moves);
+            query_answer.push(answer(game_config, world, m, player));
+        }
+
+        // backpropagation
+        let reward = winner(game_config, &query_answer).unwrap_or(vec![0_f64; player_num]);
+        for node in path {
+            tree[node].visits += 1;
+            for (sum, r) in tree[node].reward.iter_mut().zip(&reward) {
+                *sum += r;
+            }
+        }
+    }
+
+    tree[0]
+        .children
+        .iter()
+        .max_by_key(|(_, child)| tree[*child].visits)
+        .map(|(m, child)| {
+            let node = &tree[*child];
+            let values = node
+                .reward
+                .iter()
+                .map(|r| r / node.visits.max(1) as f64)
+                .collect();
+            (m.clone(), values)
+        })
+}
+
+fn ucb(node: &Node, player: Player, exploration: f64) -> f64 {
+    let visits = node.visits.max(1) as f64;
+    node.reward[player] / visits + exploration * ((node.avail.max(1) as f64).ln() / visits).sqrt()
+}
+
+impl<R> Agent for IsmctsPlayer<R>
+where
+    R: Rng + Send,
+{
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        if let Some(answer) = answerable_info(&info) {
+            return answer;
+        }
+        if let Some((m, _)) = ismcts(&info, &self.config, &mut self.rng) {
+            return m;
+        }
+        most_likely_declare(&info).unwrap_or_else(|| possible_moves[0].clone())
+    }
+}
+
+// 質問が残っていないときは一番ありそうな頭を宣言する。同じ数なら小さい手。
+// 宣言できる頭も残っていなければ None
+fn most_likely_declare(info: &Info) -> Option<Move> {
+    let possible_declare = info.movable_declare();
+    let heads: BTreeMap<Move, usize> =
+        possible_head_numed(&info.config, &info.query_answer, &info.view)
+            .into_iter()
+            .map(|(declare, num)| (Move::Declare { declare }, num))
+            .filter(|(head, _)| possible_declare.contains(head))
+            .collect();
+    heads
+        .into_iter()
+        .max_by(|(m1, n1), (m2, n2)| n1.cmp(n2).then(m2.cmp(m1)))
+        .map(|(m, _)| m)
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::{config::three_small, utils::auto_game};
+
+    use super::*;
+
+    #[test]
+    fn ismcts_plays_a_full_game() {
+        let config = three_small();
+        let game = config.gen_random(1);
+        let agents: Vec<Box<dyn Agent<Game = Game>>> = (0..config.player_num())
+            .map(|player| {
+                Box::new(IsmctsPlayer::new(
+                    SmallRng::seed_from_u64(player as u64),
+                    IsmctsConfig {
+                        iterations: 50,
+                        ..IsmctsConfig::default()
+                    },
+                )) as Box<dyn Agent<Game = Game>>
+            })
+            .collect();
+        let winner = auto_game(game, agents);
+        assert_eq!(winner.iter().sum::<usize>(), 1);
+    }
+
+    #[test]
+    fn determinization_narrows_like_answerable() {
+        // 絞り込みながら調べても、answerable で列挙し直したのと同じになる
+        let config = three_small();
+        let mut game = config.gen_random(2);
+        let (info, _) = game.info_and_move_now();
+        let world = game.distr();
+        let mut determinization = Determinization::new(&info, &world);
+        while game.is_win().is_none() {
+            let query_answer = game.history();
+            for player in 0..config.player_num() {
+                let view = cards_from_player(&world, player);
+                let heads: Vec<_> = possible_states_of(&config, &query_answer, &view, player)
+                    .map(|distr| players_head(&distr, player).clone())
+                    .collect();
+                let expected = heads
+                    .iter()
+                    .all(|head| *head == heads[0])
+                    .then(|| Move::Declare {
+                        declare: heads[0].clone(),
+                    });
+                assert_eq!(determinization.answerable(&query_answer, player), expected);
+            }
+            let player = game.player_turn();
+            let m = determinization.candidate_moves(&query_answer, player)[0].clone();
+            game.move_game(m);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use game_ai_entropy::head_frequencies;
+use game_core::{
+    abstract_game::{Agent, Candidate, ImperfectInfoGame, Rationale},
+    defs::*,
+    utils::{opponent::opponent_knowledge, possible_states},
+};
+use serde::{Deserialize, Serialize};
+
+// game-train で学習した線形モデルで打つ AI。
+// 特徴量は config の大きさによらない割合などにしてあるので、学習と違う config でも打てる。
+// 特徴量の作り方を変えたら FEATURES_VERSION を上げること。古い checkpoint は読めなくなる。
+
+pub const FEATURES_VERSION: u32 = 1;
+
+// [1, 手数 / 10, 一番多い頭の割合, 1 / 頭の候補の数, 相手が確定している確率の最大]
+pub const STATE_FEATURE_NUM: usize = 5;
+// 宣言なら [当たる確率, 1]、query なら [情報量の割合, 1] を、それぞれ状態の特徴量と掛けて並べる
+pub const MOVE_FEATURE_NUM: usize = 2 * 2 * STATE_FEATURE_NUM;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Features {
+    pub state: Vec<f64>,
+    // possible_moves と同じ順
+    pub moves: Vec<Vec<f64>>,
+    pub worlds: usize,
+}
+
+pub fn features(info: &Info, possible_moves: &[Move]) -> Features {
+    let worlds: Vec<Distr> =
+        possible_states(&info.config, &info.query_answer, &info.view).collect();
+    let heads = head_frequencies(info, &worlds);
+    let p_max = heads.first().map_or(0_f64, |(_, p)| *p);
+    let opponent_certain = opponent_knowledge(info)
+        .iter()
+        .map(|knowledge| knowledge.certain)
+        .fold(0_f64, f64::max);
+    let state = vec![
+        1_f64,
+        info.query_answer.len() as f64 / 10_f64,
+        p_max,
+        1_f64 / heads.len().max(1) as f64,
+        opponent_certain,
+    ];
+
+    let moves = possible_moves
+        .iter()
+        .map(|m| {
+            let (offset, own) = match m {
+                Move::Declare { .. } => {
+                    let p = heads
+                        .iter()
+                        .find(|(head, _)| head == m)
+                        .map_or(0_f64, |(_, p)| *p);
+                    (0, [p, 1_f64])
+                }
+                Move::Query { .. } => (2, [query_gain(info, &worlds, m), 1_f64]),
+            };
+            let mut feature = vec![0_f64; MOVE_FEATURE_NUM];
+            for (i, x) in own.iter().enumerate() {
+                for (j, s) in state.iter().enumerate() {
+                    feature[(offset + i) * STATE_FEATURE_NUM + j] = x * s;
+                }
+            }
+            feature
+        })
+        .collect();
+
+    Features {
+        state,
+        moves,
+        worlds: worlds.len(),
+    }
+}
+
+// query の答えで可能な世界の数の log がどれだけ減るかの割合（0 なら何もわからない、1 なら確定する）
+fn query_gain(info: &Info, worlds: &[Distr], q: &Move) -> f64 {
+    if worlds.len() <= 1 {
+        return 0_f64;
+    }
+    let who = info.player_turn();
+    let mut distribution = vec![0_usize; info.config.cards_num()];
+    for distr in worlds {
+        if let MoveAns::Query { ans, .. } = answer(&info.config, distr, q.clone(), who) {
+            distribution[ans] += 1;
+        }
+    }
+    let total = worlds.len() as f64;
+    let remaining: f64 = distribution
+        .into_iter()
+        .filter(|num| *num > 0)
+        .map(|num| num as f64 / total * (num as f64).log2())
+        .sum();
+    1_f64 - remaining / total.log2()
+}
+
+// 手の確率は softmax(policy・手の特徴量)、勝つ確率は sigmoid(value・状態の特徴量)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearModel {
+    pub policy: Vec<f64>,
+    pub value: Vec<f64>,
+}
+
+impl Default for LinearModel {
+    fn default() -> Self {
+        Self {
+            policy: vec![0_f64; MOVE_FEATURE_NUM],
+            value: vec![0_f64; STATE_FEATURE_NUM],
+        }
+    }
+}
+
+impl LinearModel {
+    pub fn policy(&self, features: &Features) -> Vec<f64> {
+        let scores: Vec<f64> = features
+            .moves
+            .iter()
+            .map(|feature| dot(&self.policy, feature))
+            .collect();
+        let max = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let exps: Vec<f64> = scores.iter().map(|score| (score - max).exp()).collect();
+        let sum: f64 = exps.iter().sum();
+        exps.into_iter().map(|e| e / sum).collect()
+    }
+
+    pub fn value(&self, state: &[f64]) -> f64 {
+        1_f64 / (1_f64 + (-dot(&self.value, state)).exp())
+    }
+}
+
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+// 学習の途中経過。ファイルには JSON で保存する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub features_version: u32,
+    // 学習に使った config
+    pub config: GameConfig,
+    // これまでに学習したゲームの数
+    pub games: usize,
+    pub model: LinearModel,
+}
+
+impl Checkpoint {
+    pub fn new(config: GameConfig) -> Self {
+        Self {
+            features_version: FEATURES_VERSION,
+            config,
+            games: 0,
+            model: LinearModel::default(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self).map_err(io::Error::other)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let checkpoint: Self = serde_json::from_reader(reader).map_err(io::Error::other)?;
+        if checkpoint.features_version != FEATURES_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checkpoint features version {} does not match {FEATURES_VERSION}",
+                    checkpoint.features_version
+                ),
+            ));
+        }
+        Ok(checkpoint)
+    }
+}
+
+// 確率が一番高い手を打つ
+#[derive(Debug, Clone, PartialEq)]
+pub struct LearnedPlayer {
+    model: LinearModel,
+    last: Option<Rationale<Move>>,
+}
+
+impl LearnedPlayer {
+    pub fn new(model: LinearModel) -> Self {
+        Self { model, last: None }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(Checkpoint::load(path)?.model))
+    }
+}
+
+impl Agent for LearnedPlayer {
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        let features = features(&info, &possible_moves);
+        let probs = self.model.policy(&features);
+        let mut best = 0;
+        for (i, p) in probs.iter().enumerate() {
+            if *p > probs[best] {
+                best = i;
+            }
+        }
+        let chosen = possible_moves[best].clone();
+        self.last = Some(Rationale {
+            worlds: Some(features.worlds),
+            candidates: possible_moves
+                .into_iter()
+                .zip(probs)
+                .map(|(action, value)| Candidate {
+                    kind: "policy".to_string(),
+                    action,
+                    value,
+                })
+                .collect(),
+            decision: None,
+        });
+        chosen
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use game_core::{config::three_small, utils::auto_game};
+
+    use super::*;
+
+    // 並列に走るテストとぶつからないように、毎回違う名前にする
+    fn temp_path(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!(
+            "game_ai_learned_test_{name}_{}_{nanos}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn checkpoint_round_trips() {
+        let mut checkpoint = Checkpoint::new(three_small());
+        checkpoint.model.policy[0] = 1.5;
+        let path = temp_path("checkpoint");
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path);
+        let player = LearnedPlayer::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), checkpoint);
+        assert_eq!(player.unwrap().model, checkpoint.model);
+
+        // 特徴量の作り方が違う checkpoint は読まない
+        checkpoint.features_version = FEATURES_VERSION + 1;
+        let path = temp_path("old_checkpoint");
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn learned_plays_a_full_game() {
+        let config = three_small();
+        let game = config.gen_random(1);
+        let (info, possible_moves) = game.info_and_move_now();
+        let features = features(&info, &possible_moves);
+        assert_eq!(features.state.len(), STATE_FEATURE_NUM);
+        assert!(features
+            .moves
+            .iter()
+            .all(|feature| feature.len() == MOVE_FEATURE_NUM));
+        let probs = LinearModel::default().policy(&features);
+        assert!((probs.iter().sum::<f64>() - 1_f64).abs() < 1e-9);
+
+        let mut checkpoint = Checkpoint::new(config.clone());
+        checkpoint.model.policy[0] = 1.5;
+        let player = LearnedPlayer::new(checkpoint.model);
+        let agents: Vec<Box<dyn Agent<Game = Game>>> = (0..config.player_num())
+            .map(|_| Box::new(player.clone()) as Box<dyn Agent<Game = Game>>)
+            .collect();
+        let winner = auto_game(config.gen_random(2), agents);
+        assert_eq!(winner.iter().sum::<usize>(), 1);
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+
+use game_core::{
+    abstract_game::{Agent, Candidate, ImperfectInfoGame, Player, Rationale},
+    defs::*,
+    utils::possible_states,
+};
+
+// いくつかの AI をまとめて 1 つの AI として打つ。
+// 中の AI には全員にフックを渡すので、手番で使わなかった AI も履歴はそろっている。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaMode {
+    // 全員に聞いて、重みの和が一番大きい手を打つ。同点なら先に書いた AI の手
+    Vote,
+    // 可能な世界が worlds より多いうちは 1 つ目、それ以下になったら 2 つ目の AI で打つ
+    Phase { worlds: usize },
+    // ゲームごとに 1 つ選んで打たせ、勝敗から UCB1 で選び方を学ぶ。
+    // 学んだことを使うには、同じ MetaPlayer を次のゲームでも使うこと。
+    Bandit { exploration: f64 },
+}
+
+impl MetaMode {
+    pub const DEFAULT_PHASE_WORLDS: usize = 20;
+    pub const DEFAULT_EXPLORATION: f64 = 1.0;
+}
+
+// "vote:entropy=2,unfair" / "phase=20:entropy,search" / "bandit=1.0:entropy,unfair,bayes" の形。
+// 中の AI は game-stats の strategy で、重み（省略すると 1）は vote でだけ使う。
+// params をつけるときは "phase=20:entropy,[search:depth=3]" や "vote:[unfair:risk=0.2]=2,entropy" のように [] で囲む。
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaSpec {
+    pub mode: MetaMode,
+    pub members: Vec<(String, f64)>,
+}
+
+impl MetaSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (mode, members) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("expected <mode>:<members>: {spec}"))?;
+        let (mode, param) = match mode.split_once('=') {
+            Some((mode, param)) => (mode, Some(param)),
+            None => (mode, None),
+        };
+        let mode = match (mode, param) {
+            ("vote", None) => MetaMode::Vote,
+            ("phase", param) => MetaMode::Phase {
+                worlds: parse_param(param, MetaMode::DEFAULT_PHASE_WORLDS)?,
+            },
+            ("bandit", param) => MetaMode::Bandit {
+                exploration: parse_param(param, MetaMode::DEFAULT_EXPLORATION)?,
+            },
+            _ => return Err(format!("unknown meta mode: {mode}")),
+        };
+        let members = split_members(members)?
+            .into_iter()
+            .map(parse_member)
+            .collect::<Result<Vec<_>, _>>()?;
+        if members.iter().any(|(name, _)| name.is_empty()) {
+            return Err(format!("empty member name: {spec}"));
+        }
+        if mode != MetaMode::Vote && members.iter().any(|(_, weight)| *weight != 1_f64) {
+            return Err("weights are only used by vote".into());
+        }
+        if matches!(mode, MetaMode::Phase { .. }) && members.len() != 2 {
+            return Err("phase needs exactly 2 members".into());
+        }
+        Ok(Self { mode, members })
+    }
+}
+
+// [] の外にある "," で区切る
+fn split_members(members: &str) -> Result<Vec<&str>, String> {
+    let mut split = vec![];
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (i, c) in members.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("unbalanced ]: {members}"))?
+            }
+            ',' if depth == 0 => {
+                split.push(&members[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(format!("unbalanced [: {members}"));
+    }
+    split.push(&members[start..]);
+    Ok(split)
+}
+
+// "name" / "name=2" / "[spec]" / "[spec]=2"
+fn parse_member(member: &str) -> Result<(String, f64), String> {
+    let (name, weight) = match member.strip_prefix('[') {
+        Some(rest) => {
+            let end = rest
+                .rfind(']')
+                .ok_or_else(|| format!("unbalanced [: {member}"))?;
+            let weight = match &rest[end + 1..] {
+                "" => None,
+                weight => Some(
+                    weight
+                        .strip_prefix('=')
+                        .ok_or_else(|| format!("expected ]=<weight>: {member}"))?,
+                ),
+            };
+            (&rest[..end], weight)
+        }
+        None => match member.split_once('=') {
+            Some((name, weight)) => (name, Some(weight)),
+            None => (member, None),
+        },
+    };
+    if !member.starts_with('[') && name.contains(':') {
+        return Err(format!("put a member with params in []: {member}"));
+    }
+    let weight = match weight {
+        Some(weight) => weight
+            .parse::<f64>()
+            .map_err(|_| format!("invalid weight: {member}"))?,
+        None => 1_f64,
+    };
+    Ok((name.to_string(), weight))
+}
+
+// params つきの spec は [] で囲んで書く
+fn format_member(name: &str) -> String {
+    if name.contains([':', ',', '=']) {
+        format!("[{name}]")
+    } else {
+        name.to_string()
+    }
+}
+
+fn parse_param<T: std::str::FromStr>(param: Option<&str>, default: T) -> Result<T, String> {
+    match param {
+        Some(param) => param
+            .parse()
+            .map_err(|_| format!("invalid meta parameter: {param}")),
+        None => Ok(default),
+    }
+}
+
+impl fmt::Display for MetaSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            MetaMode::Vote => write!(f, "vote:")?,
+            MetaMode::Phase { worlds } => write!(f, "phase={worlds}:")?,
+            MetaMode::Bandit { exploration } => write!(f, "bandit={exploration}:")?,
+        }
+        let members: Vec<String> = self
+            .members
+            .iter()
+            .map(|(name, weight)| match self.mode {
+                MetaMode::Vote => format!("{}={weight}", format_member(name)),
+                _ => format_member(name),
+            })
+            .collect();
+        write!(f, "{}", members.join(","))
+    }
+}
+
+// bandit で数えている、中の AI ごとの使った回数と勝った回数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArmStats {
+    pub games: usize,
+    pub wins: usize,
+}
+
+pub struct MetaPlayer {
+    mode: MetaMode,
+    members: Vec<Member>,
+    // Phase と Bandit で、今打たせている AI
+    active: usize,
+    seat: Player,
+    last: Option<Rationale<Move>>,
+}
+
+struct Member {
+    name: String,
+    weight: f64,
+    agent: Box<dyn Agent<Game = Game>>,
+    stats: ArmStats,
+}
+
+impl MetaPlayer {
+    // build には中の AI の名前が渡されるので、その AI を作って返す
+    pub fn new(
+        spec: &MetaSpec,
+        mut build: impl FnMut(&str) -> Box<dyn Agent<Game = Game>>,
+    ) -> Self {
+        assert!(!spec.members.is_empty(), "中の AI がいない");
+        let members = spec
+            .members
+            .iter()
+            .map(|(name, weight)| Member {
+                name: name.clone(),
+                weight: *weight,
+                agent: build(name),
+                stats: ArmStats::default(),
+            })
+            .collect();
+        Self {
+            mode: spec.mode.clone(),
+            members,
+            active: 0,
+            seat: 0,
+            last: None,
+        }
+    }
+
+    pub fn arm_stats(&self) -> Vec<(&str, &ArmStats)> {
+        self.members
+            .iter()
+            .map(|member| (member.name.as_str(), &member.stats))
+            .collect()
+    }
+
+    fn choose_arm(&self, exploration: f64) -> usize {
+        if let Some(unplayed) = self.members.iter().position(|m| m.stats.games == 0) {
+            return unplayed;
+        }
+        let total: usize = self.members.iter().map(|m| m.stats.games).sum();
+        let ucb = |stats: &ArmStats| {
+            let games = stats.games as f64;
+            stats.wins as f64 / games + exploration * ((total as f64).ln() / games).sqrt()
+        };
+        let mut best = 0;
+        for (i, member) in self.members.iter().enumerate() {
+            if ucb(&member.stats) > ucb(&self.members[best].stats) {
+                best = i;
+            }
+        }
+        best
+    }
+
+    fn vote(&mut self, info: Info, possible_moves: Vec<Move>) -> Move {
+        // 最初に出てきた順を保つ
+        let mut votes: Vec<(Move, f64)> = vec![];
+        for member in self.members.iter_mut() {
+            let m = member.agent.use_info(info.clone(), possible_moves.clone());
+            match votes.iter_mut().find(|(voted, _)| *voted == m) {
+                Some((_, weight)) => *weight += member.weight,
+                None => votes.push((m, member.weight)),
+            }
+        }
+        let mut best = 0;
+        for (i, (_, weight)) in votes.iter().enumerate() {
+            if *weight > votes[best].1 {
+                best = i;
+            }
+        }
+        let chosen = votes[best].0.clone();
+        self.last = Some(Rationale {
+            candidates: votes
+                .into_iter()
+                .map(|(action, value)| Candidate {
+                    kind: "vote".to_string(),
+                    action,
+                    value,
+                })
+                .collect(),
+            ..Default::default()
+        });
+        chosen
+    }
+}
+
+impl Agent for MetaPlayer {
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        match self.mode {
+            MetaMode::Vote => return self.vote(info, possible_moves),
+            MetaMode::Phase { worlds } => {
+                let now = possible_states(&info.config, &info.query_answer, &info.view).count();
+                self.active = if now > worlds { 0 } else { 1 };
+            }
+            MetaMode::Bandit { .. } => {}
+        }
+        let agent = &mut self.members[self.active].agent;
+        let m = agent.use_info(info, possible_moves);
+        self.last = agent.rationale();
+        m
+    }
+
+    fn on_game_start(&mut self, config: &GameConfig, seat: Player) {
+        self.seat = seat;
+        self.active = match self.mode {
+            MetaMode::Bandit { exploration } => self.choose_arm(exploration),
+            _ => 0,
+        };
+        self.last = None;
+        for member in self.members.iter_mut() {
+            member.agent.on_game_start(config, seat);
+        }
+    }
+
+    fn observe(&mut self, move_ans: &MoveAns) {
+        for member in self.members.iter_mut() {
+            member.agent.observe(move_ans);
+        }
+    }
+
+    fn on_game_end(&mut self, outcome: &[usize]) {
+        if matches!(self.mode, MetaMode::Bandit { .. }) {
+            let stats = &mut self.members[self.active].stats;
+            stats.games += 1;
+            stats.wins += outcome[self.seat];
+        }
+        for member in self.members.iter_mut() {
+            member.agent.on_game_end(outcome);
+        }
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::config::three_small;
+
+    use super::*;
+
+    // 合法手の先頭か末尾を打つ
+    struct Fixed(bool);
+
+    impl Agent for Fixed {
+        type Game = Game;
+        fn use_info(&mut self, _info: Info, possible_moves: Vec<Move>) -> Move {
+            if self.0 {
+                possible_moves[0].clone()
+            } else {
+                possible_moves.last().unwrap().clone()
+            }
+        }
+    }
+
+    fn build(name: &str) -> Box<dyn Agent<Game = Game>> {
+        Box::new(Fixed(name == "first"))
+    }
+
+    #[test]
+    fn vote_and_bandit() {
+        let spec = MetaSpec::parse("vote:first,last=2,first").unwrap();
+        assert_eq!(spec.to_string(), "vote:first=1,last=2,first=1");
+        assert!(MetaSpec::parse("phase:first").is_err());
+        assert!(MetaSpec::parse("bandit=x:first").is_err());
+        // params つきの AI は [] で囲む
+        let bracketed = MetaSpec::parse("vote:[first:a=1,b=2]=2,last").unwrap();
+        assert_eq!(
+            bracketed.members,
+            [
+                ("first:a=1,b=2".to_string(), 2_f64),
+                ("last".to_string(), 1_f64)
+            ]
+        );
+        assert_eq!(bracketed.to_string(), "vote:[first:a=1,b=2]=2,last=1");
+        assert_eq!(MetaSpec::parse(&bracketed.to_string()).unwrap(), bracketed);
+        assert!(MetaSpec::parse("phase:first:a=1,last").is_err());
+        assert!(MetaSpec::parse("phase:[first:a=1,last").is_err());
+
+        let game = three_small().gen_random(0);
+        let (info, possible_moves) = game.info_and_move_now();
+        // 同点なら先に出てきた手
+        let mut meta = MetaPlayer::new(&spec, build);
+        assert_eq!(
+            meta.use_info(info.clone(), possible_moves.clone()),
+            possible_moves[0]
+        );
+        let spec = MetaSpec::parse("vote:first,last=2.5").unwrap();
+        let mut meta = MetaPlayer::new(&spec, build);
+        assert_eq!(
+            meta.use_info(info, possible_moves.clone()),
+            *possible_moves.last().unwrap()
+        );
+        assert_eq!(meta.rationale().unwrap().candidates.len(), 2);
+
+        // まだ使っていない AI から順に試し、勝った方を選ぶようになる
+        let spec = MetaSpec::parse("bandit=0.1:first,last").unwrap();
+        let mut meta = MetaPlayer::new(&spec, build);
+        let config = three_small();
+        for (win, expected) in [(0, 0), (1, 1), (0, 1), (1, 1)] {
+            meta.on_game_start(&config, 2);
+            assert_eq!(meta.active, expected);
+            meta.on_game_end(&[0, 0, win]);
+        }
+        let stats = meta.arm_stats();
+        assert_eq!(stats[1].1, &ArmStats { games: 3, wins: 2 });
+    }
+}
@@ -1,5 +1,5 @@
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, ImperfectInfoGame, PolicyAgent},
     defs::Game,
     utils::{answerable_info, random_vec},
 };
@@ -34,28 +34,64 @@ impl Default for RandomPlayer<SmallRng> {
 
 impl<R> Agent for RandomPlayer<R>
 where
-    R: Rng,
+    R: Rng + Send,
 {
     type Game = Game;
 
     fn use_info(
         &mut self,
         info: <Self::Game as ImperfectInfoGame>::Info,
-        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
     ) -> <Self::Game as ImperfectInfoGame>::Move {
         if let Some(answer) = answerable_info(&info) {
             return answer;
         }
-        let possible_moves = info.movable_query();
-        if possible_moves.is_empty() {
-            let possible_declare = info.movable_declare();
-            possible_declare.into_iter().next().unwrap()
+        let queries = info.movable_query();
+        if queries.is_empty() {
+            // 宣言も残っていなければ、渡された手から選ぶ
+            match info.movable_declare().into_iter().next() {
+                Some(declare) => declare,
+                None => random_vec(&mut self.rng, possible_moves),
+            }
         } else {
-            random_vec(&mut self.rng, possible_moves.into_iter().collect())
+            random_vec(&mut self.rng, queries.into_iter().collect())
         }
     }
 }
 
+// use_info と同じ打ち方を分布として返す。
+impl<R> PolicyAgent for RandomPlayer<R>
+where
+    R: Rng,
+{
+    type Game = Game;
+
+    fn policy(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> Vec<(<Self::Game as ImperfectInfoGame>::Move, f64)> {
+        if let Some(answer) = answerable_info(&info) {
+            return vec![(answer, 1_f64)];
+        }
+        let queries = info.movable_query();
+        if queries.is_empty() {
+            // 宣言も残っていなければ、渡された手から一様に選ぶ
+            match info.movable_declare().into_iter().next() {
+                Some(declare) => vec![(declare, 1_f64)],
+                None => uniform(possible_moves),
+            }
+        } else {
+            uniform(queries.into_iter().collect())
+        }
+    }
+}
+
+fn uniform<M>(moves: Vec<M>) -> Vec<(M, f64)> {
+    let p = 1_f64 / moves.len() as f64;
+    moves.into_iter().map(|m| (m, p)).collect()
+}
+
 fn time_seed() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
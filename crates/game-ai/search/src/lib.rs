@@ -1,92 +1,192 @@
-use std::collections::HashSet;
-
+use game_artifact::{book_rationale, ArtifactStore, BookCache};
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, Candidate, Decision, ImperfectInfoGame, Player, Rationale},
     defs::*,
     utils::*,
 };
 
-pub fn search_depth(info: &Info, depth: usize) -> Option<(Move, Vec<f64>)> {
+// 各 seat の勝率を v[player] に持つ。
+// 手番のプレイヤーは自分の view から見える世界（情報集合）だけをもとに手を選び、
+// その手の結果は、選ばれた世界の中で評価する。
+pub fn search_depth(info: &Info, depth: usize, samples: usize) -> Option<(Move, Vec<f64>)> {
     let mut query_answer = info.query_answer.clone();
-    let movables = (0..info.config.player_num())
-        .map(|player| movable_query_ref(&info.config, &query_answer, player).collect())
-        .collect();
-    search_rec(
-        &info.config,
-        &mut query_answer,
-        &info.view,
-        depth,
-        &movables,
-    )
+    search_rec(&info.config, &mut query_answer, &info.view, depth, samples)
 }
 
+// 手番のプレイヤーの決定。
+// 可能な世界の全体で頭が確定していれば宣言する。
+// そうでなければ view から可能な世界を samples 個まで取り出し、各 query について世界ごとの勝率の平均をとって、
+// 自分の勝率が最大（同じなら他のプレイヤーの勝率の和が最小）の手を選ぶ。
 pub fn search_rec(
     config: &GameConfig,
     query_answer: &mut Vec<MoveAns>,
     view: &View,
     depth: usize,
-    movables: &Vec<HashSet<Move>>,
+    samples: usize,
 ) -> Option<(Move, Vec<f64>)> {
     let player_num = config.player_num();
-    if let Some(answer) = answerable(config, query_answer, view) {
+    let now_player = config.player_turn(query_answer.len());
+    let worlds: Vec<Distr> = possible_states(config, query_answer, view).collect();
+
+    if let Some(answer) = certain_declare(&worlds, now_player) {
         let mut v = vec![0_f64; player_num];
-        v[0] = 1_f64;
+        v[now_player] = 1_f64;
         return Some((answer, v));
     }
 
     if depth == 0 {
-        None
-    } else {
-        let now_player = config.player_turn(query_answer.len());
-        let next_player = config.player_turn(query_answer.len() + 1);
-        let possible_state: Vec<_> = possible_states(config, query_answer, view).collect();
-        let state_len = possible_state.len();
-
-        let mut min: Option<(&Move, Vec<f64>)> = None;
-        for m in &movables[now_player] {
-            let mut points = vec![0_f64; player_num];
-            for distr in &possible_state {
-                let ans = answer(config, distr, m.clone(), now_player);
-                query_answer.push(ans);
-                let view = cards_from_player(distr, next_player);
-                let res = search_rec(config, query_answer, &view, depth - 1, movables);
-                query_answer.pop();
-                let Some((_, mut point)) = res else {
-                    continue;
-                };
-                point.rotate_right(1);
-                for i in 0..player_num {
-                    points[i] += point[i];
-                }
-            }
+        return None;
+    }
 
-            for v in &mut points {
-                *v /= state_len as f64;
+    best_of(
+        query_values(config, query_answer, worlds, depth, samples),
+        now_player,
+    )
+}
+
+// 手番のプレイヤーの各 query について、worlds から samples 個まで取り出した世界ごとの勝率の平均。
+pub fn query_values(
+    config: &GameConfig,
+    query_answer: &mut Vec<MoveAns>,
+    worlds: Vec<Distr>,
+    depth: usize,
+    samples: usize,
+) -> Vec<(Move, Vec<f64>)> {
+    let player_num = config.player_num();
+    let now_player = config.player_turn(query_answer.len());
+    let worlds = sample_states(worlds, samples);
+    let movables: Vec<Move> = movable_query_ref(config, query_answer, now_player).collect();
+    let mut values = vec![];
+    for m in movables {
+        let mut points = vec![0_f64; player_num];
+        for distr in &worlds {
+            let ans = answer(config, distr, m.clone(), now_player);
+            query_answer.push(ans);
+            let point = evaluate_in_world(config, query_answer, distr, depth - 1, samples);
+            query_answer.pop();
+            for i in 0..player_num {
+                points[i] += point[i];
             }
+        }
 
-            match &min {
-                None => min = Some((m, points)),
-                Some(x) => {
-                    if x.1[0] <= points[0] {
-                        min = Some((m, points))
-                    }
-                }
+        for v in &mut points {
+            *v /= worlds.len() as f64;
+        }
+        values.push((m, points));
+    }
+    values
+}
+
+// 自分の勝率が最大（同じなら他のプレイヤーの勝率の和が最小）の手
+fn best_of(values: Vec<(Move, Vec<f64>)>, now_player: Player) -> Option<(Move, Vec<f64>)> {
+    let mut best: Option<(Move, Vec<f64>)> = None;
+    for (m, points) in values {
+        let better = match &best {
+            None => true,
+            Some((_, x)) => {
+                let others = |v: &[f64]| v.iter().sum::<f64>() - v[now_player];
+                x[now_player] < points[now_player]
+                    || (x[now_player] == points[now_player] && others(&points) < others(x))
             }
+        };
+        if better {
+            best = Some((m, points));
         }
+    }
+
+    best
+}
 
-        min.map(|(m, v)| (m.clone(), v))
+// 世界 distr が本当の配置であるとして、手番のプレイヤーがその人の view から決めた手を進め、各 seat の勝率を返す。
+// depth 以内に決着がつかない場合は全員 0 とする。
+pub fn evaluate_in_world(
+    config: &GameConfig,
+    query_answer: &mut Vec<MoveAns>,
+    distr: &Distr,
+    depth: usize,
+    samples: usize,
+) -> Vec<f64> {
+    let player_num = config.player_num();
+    let now_player = config.player_turn(query_answer.len());
+    let view = cards_from_player(distr, now_player);
+    let Some((m, _)) = search_rec(config, query_answer, &view, depth, samples) else {
+        return vec![0_f64; player_num];
+    };
+
+    let ans = answer(config, distr, m, now_player);
+    if let MoveAns::Declare { ans: true, .. } = ans {
+        let mut v = vec![0_f64; player_num];
+        v[now_player] = 1_f64;
+        return v;
+    }
+    // depth == 0 では確実な宣言しか返ってこないので、ここに来るのは depth > 0 のとき
+    query_answer.push(ans);
+    let v = evaluate_in_world(config, query_answer, distr, depth - 1, samples);
+    query_answer.pop();
+    v
+}
+
+// 等間隔に samples 個まで取り出す。
+fn sample_states(worlds: Vec<Distr>, samples: usize) -> Vec<Distr> {
+    if samples == 0 || worlds.len() <= samples {
+        return worlds;
+    }
+    let len = worlds.len();
+    (0..samples)
+        .map(|i| worlds[i * len / samples].clone())
+        .collect()
+}
+
+fn certain_declare(worlds: &[Distr], player: Player) -> Option<Move> {
+    let (first, rest) = worlds.split_first()?;
+    let head = players_head(first, player);
+    if rest.iter().all(|distr| players_head(distr, player) == head) {
+        Some(Move::Declare {
+            declare: head.clone(),
+        })
+    } else {
+        None
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SearchPlayer {
     depth: usize,
+    samples: usize,
+    last: Option<Rationale<Move>>,
+    book: Option<BookCache>,
 }
 
 impl SearchPlayer {
+    pub const DEFAULT_SAMPLES: usize = 4;
+    // 手の選び方を変えたら上げる
+    pub const BOOK_VERSION: u32 = 1;
+
     // depth >= 5 はあまりにも時間を使うので危険
     pub fn new(depth: usize) -> SearchPlayer {
-        SearchPlayer { depth }
+        SearchPlayer::with_samples(depth, Self::DEFAULT_SAMPLES)
+    }
+
+    // samples = 0 なら可能な世界を全部使う
+    pub fn with_samples(depth: usize, samples: usize) -> SearchPlayer {
+        SearchPlayer {
+            depth,
+            samples,
+            last: None,
+            book: None,
+        }
+    }
+
+    // 序盤の本の名前。depth と samples で手が変わるので名前に入れる
+    pub fn book_agent(depth: usize, samples: usize) -> String {
+        format!("search-d{depth}-s{samples}")
+    }
+
+    // store に本があれば、序盤はそれを引く
+    pub fn with_book(mut self, store: ArtifactStore) -> Self {
+        let agent = Self::book_agent(self.depth, self.samples);
+        self.book = Some(BookCache::new(store, &agent, Self::BOOK_VERSION));
+        self
     }
 }
 
@@ -99,11 +199,75 @@ impl Agent for SearchPlayer {
         possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
     ) -> <Self::Game as ImperfectInfoGame>::Move {
         if let Some(answer) = answerable_info(&info) {
+            self.last = Some(Rationale {
+                decision: Some(Decision {
+                    declare: true,
+                    values: vec![("p".to_string(), 1_f64)],
+                }),
+                ..Default::default()
+            });
             return answer;
         }
-        if let Some((m, _)) = search_depth(&info, self.depth) {
-            return m;
+        if let Some(m) = self.book.as_mut().and_then(|book| book.lookup(&info)) {
+            if possible_moves.contains(&m) {
+                self.last = Some(book_rationale(&m));
+                return m;
+            }
+        }
+        if self.depth == 0 {
+            self.last = None;
+            return possible_moves.into_iter().next().unwrap();
         }
-        possible_moves.into_iter().next().unwrap()
+
+        let me = info.player_turn();
+        let worlds: Vec<Distr> =
+            possible_states(&info.config, &info.query_answer, &info.view).collect();
+        let worlds_num = worlds.len();
+        let mut query_answer = info.query_answer.clone();
+        let values = query_values(
+            &info.config,
+            &mut query_answer,
+            worlds,
+            self.depth,
+            self.samples,
+        );
+        self.last = Some(Rationale {
+            worlds: Some(worlds_num),
+            candidates: values
+                .iter()
+                .map(|(m, v)| Candidate {
+                    kind: "search_value".to_string(),
+                    action: m.clone(),
+                    value: v[me],
+                })
+                .collect(),
+            decision: None,
+        });
+        match best_of(values, me) {
+            Some((m, _)) => m,
+            None => possible_moves.into_iter().next().unwrap(),
+        }
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::config::three_small;
+
+    use super::*;
+
+    #[test]
+    fn search_values_are_win_probabilities_per_seat() {
+        let config = three_small();
+        let game = config.gen_random(3);
+        let (info, _) = game.info_and_move_now();
+        let (_, values) = search_depth(&info, 2, 0).unwrap();
+        assert_eq!(values.len(), config.player_num());
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        assert!(values.iter().sum::<f64>() <= 1.0 + 1e-9);
     }
 }
@@ -1,25 +1,36 @@
-use game_ai_entropy::entoropy;
+use game_ai_entropy::{
+    best_query, certain_rationale, entropy_rationale, head_frequencies, query_scores,
+};
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, ImperfectInfoGame, Player, Rationale},
     defs::*,
-    utils::*,
+    utils::{declare::DeclarePolicy, *},
 };
 
+// 最初の手は entropy で質問し、そのあとは DeclarePolicy が宣言すると決めるまで entropy で質問する。
 #[derive(Debug, Clone, PartialEq)]
 pub struct Unfair {
     first: bool,
-    attack: f64,
+    policy: DeclarePolicy,
+    last: Option<Rationale<Move>>,
 }
 
 impl Unfair {
-    pub fn new(attack: f64) -> Self {
+    pub fn new(policy: DeclarePolicy) -> Self {
         Unfair {
             first: true,
-            attack,
+            policy,
+            last: None,
         }
     }
 }
 
+impl Default for Unfair {
+    fn default() -> Self {
+        Self::new(DeclarePolicy::default())
+    }
+}
+
 impl Agent for Unfair {
     type Game = Game;
 
@@ -29,30 +40,39 @@ impl Agent for Unfair {
         _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
     ) -> <Self::Game as ImperfectInfoGame>::Move {
         if let Some(answer) = answerable(&info.config, &info.query_answer, &info.view) {
+            self.last = Some(certain_rationale());
             return answer;
         }
+
+        let distrs: Vec<_> =
+            possible_states(&info.config, &info.query_answer, &info.view).collect();
+        let scores = query_scores(&info, &distrs);
+        let mut rationale =
+            entropy_rationale(distrs.len(), &scores, &head_frequencies(&info, &distrs));
         if self.first {
             self.first = false;
-            return entoropy(info).unwrap();
+            if let Some(q) = best_query(scores.clone()) {
+                self.last = Some(rationale);
+                return q;
+            }
         }
 
-        let head_numed = possible_head_numed(&info.config, &info.query_answer, &info.view);
-        assert!(!head_numed.is_empty());
-        let mut num_all = 0;
-        let (a, num) = head_numed
-            .into_iter()
-            .map(|(declare, num)| {
-                num_all += num;
-                let m = Move::Declare { declare };
-                (m, num)
-            })
-            .max_by_key(|(_, n)| *n)
-            .unwrap();
-
-        if (num as f64 / num_all as f64) >= self.attack {
-            a
-        } else {
-            entoropy(info).unwrap()
+        // 確定していないので、まだ宣言していない本当の頭が残っている
+        let evaluation = self.policy.evaluate(&info).unwrap();
+        rationale.decision = Some(evaluation.decision());
+        self.last = Some(rationale);
+        if evaluation.declare {
+            return evaluation.best;
         }
+        // 情報が得られる質問がないときは一番ありそうな頭を宣言する
+        best_query(scores).unwrap_or(evaluation.best)
+    }
+
+    fn on_game_start(&mut self, _config: &GameConfig, _seat: Player) {
+        self.first = true;
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
     }
 }
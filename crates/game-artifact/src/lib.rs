@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use itertools::Itertools;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use game_core::{
+    abstract_game::{Candidate, Player, Rationale},
+    defs::*,
+    utils::{movable_query_ref, possible_states_of},
+};
+
+// AI ごとの事前計算の結果をファイルに置いておく。
+// game-core はゲームの規則だけにしておきたいので、ファイルを読み書きするものはこの crate に置く。
+// (AI の名前, AI の版, config のハッシュ, 成果物の名前) で場所が決まるので、
+// AI の振る舞いを変えたら版を上げれば古い成果物は使われなくなる。
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ArtifactKey {
+    pub agent: String,
+    pub version: u32,
+    pub config_hash: String,
+    pub name: String,
+}
+
+impl ArtifactKey {
+    pub fn new(agent: &str, version: u32, config: &GameConfig, name: &str) -> Self {
+        Self {
+            agent: agent.to_string(),
+            version,
+            config_hash: config_hash(config),
+            name: name.to_string(),
+        }
+    }
+}
+
+// config の JSON の FNV-1a。実行環境によらず同じ値になる。
+pub fn config_hash(config: &GameConfig) -> String {
+    let json = serde_json::to_string(config).expect("failed to serialize config");
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in json.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+impl ArtifactStore {
+    pub const ENV: &'static str = "GAME_ARTIFACTS";
+
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // 環境変数 GAME_ARTIFACTS のディレクトリ。なければ ./artifacts
+    pub fn from_env() -> Self {
+        Self::new(std::env::var(Self::ENV).unwrap_or_else(|_| "artifacts".to_string()))
+    }
+
+    pub fn path(&self, key: &ArtifactKey) -> PathBuf {
+        self.root
+            .join(&key.agent)
+            .join(format!("v{}", key.version))
+            .join(&key.config_hash)
+            .join(format!("{}.json", key.name))
+    }
+
+    pub fn save<T: Serialize>(&self, key: &ArtifactKey, value: &T) -> io::Result<()> {
+        let path = self.path(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, value).map_err(io::Error::other)
+    }
+
+    // まだ作られていなければ Ok(None)
+    pub fn load<T: DeserializeOwned>(&self, key: &ArtifactKey) -> io::Result<Option<T>> {
+        let file = match File::open(self.path(key)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(io::Error::other)
+    }
+}
+
+// 序盤の情報集合ごとに、AI が選ぶ手を前もって計算しておいたもの。
+// turns 手目まで（履歴の長さが turns 未満）の、query だけからなる履歴の情報集合を全部持つ。
+// 宣言が外れた履歴は入れていないので、そのときは AI が自分で計算する。
+// 情報集合の数は view の数 × 履歴の数なので、three_midium なら turns = 1 くらいにしておく。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBook {
+    pub config: GameConfig,
+    pub turns: usize,
+    pub table: HashMap<String, Move>,
+}
+
+impl OpeningBook {
+    pub const ARTIFACT_NAME: &'static str = "opening_book";
+
+    // policy は本を使わずに計算する AI の手
+    pub fn build(
+        config: &GameConfig,
+        turns: usize,
+        mut policy: impl FnMut(Info, Vec<Move>) -> Move,
+    ) -> Self {
+        let mut table = HashMap::new();
+        for turn in 0..turns {
+            let player = config.player_turn(turn);
+            for view in all_views(config, player) {
+                let mut query_answer = vec![];
+                extend_histories(
+                    config,
+                    &view,
+                    player,
+                    &mut query_answer,
+                    turn,
+                    &mut |info| {
+                        let possible_moves = info
+                            .movable_query()
+                            .into_iter()
+                            .chain(info.movable_declare())
+                            .collect();
+                        let key = book_key(&info.view, &info.query_answer);
+                        table.insert(key, policy(info, possible_moves));
+                    },
+                );
+            }
+        }
+        Self {
+            config: config.clone(),
+            turns,
+            table,
+        }
+    }
+
+    pub fn lookup(&self, info: &Info) -> Option<&Move> {
+        if info.config != self.config || info.query_answer.len() >= self.turns {
+            return None;
+        }
+        self.table.get(&book_key(&info.view, &info.query_answer))
+    }
+}
+
+pub fn book_key(view: &View, query_answer: &[MoveAns]) -> String {
+    serde_json::to_string(&(view, query_answer)).expect("failed to serialize info set")
+}
+
+// player から見える view の全体
+fn all_views(config: &GameConfig, player: Player) -> Vec<View> {
+    let mut views = vec![];
+    for hand in config
+        .all_cards()
+        .into_iter()
+        .combinations(config.hand_num())
+    {
+        let rest: Vec<Card> = config
+            .all_cards()
+            .into_iter()
+            .filter(|card| !hand.contains(card))
+            .collect();
+        let mut other = vec![None; config.player_num()];
+        heads_of_others(config, player, 0, &rest, &mut other, &mut |other| {
+            views.push(View {
+                hand: hand.iter().cloned().collect(),
+                other: other.to_vec(),
+            })
+        });
+    }
+    views
+}
+
+fn heads_of_others(
+    config: &GameConfig,
+    me: Player,
+    player: Player,
+    rest: &[Card],
+    other: &mut Vec<Option<std::collections::BTreeSet<Card>>>,
+    f: &mut impl FnMut(&[Option<std::collections::BTreeSet<Card>>]),
+) {
+    if player == config.player_num() {
+        f(other);
+        return;
+    }
+    if player == me {
+        heads_of_others(config, me, player + 1, rest, other, f);
+        return;
+    }
+    for head in rest.iter().cloned().combinations(config.head_num()) {
+        let rest: Vec<Card> = rest
+            .iter()
+            .cloned()
+            .filter(|card| !head.contains(card))
+            .collect();
+        other[player] = Some(head.into_iter().collect());
+        heads_of_others(config, me, player + 1, &rest, other, f);
+    }
+    other[player] = None;
+}
+
+// owner の view と矛盾しない、query だけからなる長さ remaining の履歴を順に足して f に渡す
+fn extend_histories(
+    config: &GameConfig,
+    view: &View,
+    owner: Player,
+    query_answer: &mut Vec<MoveAns>,
+    remaining: usize,
+    f: &mut impl FnMut(Info),
+) {
+    if possible_states_of(config, query_answer, view, owner)
+        .next()
+        .is_none()
+    {
+        return;
+    }
+    if remaining == 0 {
+        f(Info {
+            config: config.clone(),
+            query_answer: query_answer.clone(),
+            view: view.clone(),
+        });
+        return;
+    }
+    let who = config.player_turn(query_answer.len());
+    let max_ans = config.hand_num() + (config.player_num() - 1) * config.head_num();
+    let queries: Vec<Move> = movable_query_ref(config, query_answer, who).collect();
+    for q in queries {
+        let Move::Query {
+            query_to,
+            query_sort,
+        } = q
+        else {
+            continue;
+        };
+        for ans in 0..=max_ans {
+            query_answer.push(MoveAns::Query {
+                who,
+                query_to,
+                query_sort: query_sort.clone(),
+                ans,
+            });
+            extend_histories(config, view, owner, query_answer, remaining - 1, f);
+            query_answer.pop();
+        }
+    }
+}
+
+// AI に持たせて、本を引く。本は最初に引いたときに store から読み、config ごとに覚えておく。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookCache {
+    store: ArtifactStore,
+    agent: String,
+    version: u32,
+    loaded: Option<(GameConfig, Option<Arc<OpeningBook>>)>,
+}
+
+impl BookCache {
+    pub fn new(store: ArtifactStore, agent: &str, version: u32) -> Self {
+        Self {
+            store,
+            agent: agent.to_string(),
+            version,
+            loaded: None,
+        }
+    }
+
+    pub fn key(&self, config: &GameConfig) -> ArtifactKey {
+        ArtifactKey::new(
+            &self.agent,
+            self.version,
+            config,
+            OpeningBook::ARTIFACT_NAME,
+        )
+    }
+
+    pub fn store(&self) -> &ArtifactStore {
+        &self.store
+    }
+
+    // 本がない・読めないときは None（AI は自分で計算する）
+    pub fn lookup(&mut self, info: &Info) -> Option<Move> {
+        if self.loaded.as_ref().map(|(config, _)| config) != Some(&info.config) {
+            let book = self
+                .store
+                .load::<OpeningBook>(&self.key(&info.config))
+                .ok()
+                .flatten()
+                .map(Arc::new);
+            self.loaded = Some((info.config.clone(), book));
+        }
+        let (_, book) = self.loaded.as_ref()?;
+        book.as_ref()?.lookup(info).cloned()
+    }
+}
+
+// 本から引いた手の理由
+pub fn book_rationale(m: &Move) -> Rationale<Move> {
+    Rationale {
+        candidates: vec![Candidate {
+            kind: "book".to_string(),
+            action: m.clone(),
+            value: 1_f64,
+        }],
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::{abstract_game::ImperfectInfoGame, config::three_small};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn opening_book_round_trips_through_the_store() {
+        let config = three_small();
+        // 先頭の合法手を選ぶ AI の本
+        let book = OpeningBook::build(&config, 2, |_, moves| moves[0].clone());
+        assert!(!book.table.is_empty());
+
+        // 前に落ちた実行の残りを踏まないように、実行ごとに別のディレクトリを使う
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root =
+            std::env::temp_dir().join(format!("game_artifact_test_{}_{nanos}", std::process::id()));
+        let mut cache = BookCache::new(ArtifactStore::new(&root), "first", 1);
+        let game = config.gen_random(4);
+        let (info, moves) = game.info_and_move_now();
+        assert_eq!(cache.lookup(&info), None);
+
+        let mut cache = BookCache::new(ArtifactStore::new(&root), "first", 1);
+        cache.store().save(&cache.key(&config), &book).unwrap();
+        assert_eq!(cache.lookup(&info), Some(moves[0].clone()));
+        // 版が違えば使わない
+        let mut other = BookCache::new(ArtifactStore::new(&root), "first", 2);
+        assert_eq!(other.lookup(&info), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,9 +1,14 @@
+use serde::{Deserialize, Serialize};
+
 ///  Player(i) がいるなら j < i に対して Player(j) もいること
 pub type Player = usize;
 
 pub trait ImperfectInfoGame {
     type Info;
     type Move;
+    type Config;
+    // 全員に公開される手とその答え
+    type MoveAns;
     // how many player there is
     fn player_number(&self) -> usize;
     // who should play
@@ -11,13 +16,136 @@ pub trait ImperfectInfoGame {
     fn info_and_move_now(&self) -> (Self::Info, Vec<Self::Move>);
     fn move_game(&mut self, m: Self::Move) -> bool;
     fn is_win(&self) -> Option<Vec<usize>>;
+    fn config(&self) -> Self::Config;
+    fn history(&self) -> Vec<Self::MoveAns>;
 }
 
-pub trait Agent {
+pub trait Agent: Send {
     type Game: ImperfectInfoGame;
     fn use_info(
         &mut self,
         info: <Self::Game as ImperfectInfoGame>::Info,
         possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
     ) -> <Self::Game as ImperfectInfoGame>::Move;
+
+    // ゲームの最初に一度呼ばれる。同じ AI を次のゲームでも使うなら、ここで状態を戻す。
+    fn on_game_start(
+        &mut self,
+        _config: &<Self::Game as ImperfectInfoGame>::Config,
+        _seat: Player,
+    ) {
+    }
+    // 自分の手も含めて、指された手とその答えが順に渡される。
+    fn observe(&mut self, _move_ans: &<Self::Game as ImperfectInfoGame>::MoveAns) {}
+    // outcome は is_win と同じ形
+    fn on_game_end(&mut self, _outcome: &[usize]) {}
+
+    // 直前の use_info でその手を選んだ理由。説明できない AI は None のまま。
+    fn rationale(&self) -> Option<Rationale<<Self::Game as ImperfectInfoGame>::Move>> {
+        None
+    }
+}
+
+// AI が手を選んだ理由。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rationale<M> {
+    // 考えた可能な世界の数
+    pub worlds: Option<usize>,
+    pub candidates: Vec<Candidate<M>>,
+    pub decision: Option<Decision>,
+}
+
+impl<M> Default for Rationale<M> {
+    fn default() -> Self {
+        Self {
+            worlds: None,
+            candidates: vec![],
+            decision: None,
+        }
+    }
+}
+
+// 候補の手とその値。kind は値の意味（"entropy", "head_frequency", "search_value", "probability" など）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate<M> {
+    pub kind: String,
+    pub action: M,
+    pub value: f64,
+}
+
+// 宣言するかどうかの判断と、判断に使った値（"p", "wait", "risk" など）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub declare: bool,
+    pub values: Vec<(String, f64)>,
+}
+
+// 試合の記録に残すときの形
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRationale<M> {
+    pub turn: usize,
+    pub player: Player,
+    pub rationale: Rationale<M>,
+}
+
+// 混合戦略で打つ AI。possible_moves の上の確率分布を返す（確率の和は 1）。
+pub trait PolicyAgent {
+    type Game: ImperfectInfoGame;
+    fn policy(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> Vec<(<Self::Game as ImperfectInfoGame>::Move, f64)>;
+}
+
+// PolicyAgent の分布から乱数で 1 手選んで Agent として打つ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sampling<P, R> {
+    agent: P,
+    rng: R,
+}
+
+impl<P, R> Sampling<P, R>
+where
+    P: PolicyAgent,
+    R: rand::Rng,
+{
+    pub fn new(agent: P, rng: R) -> Self {
+        Self { agent, rng }
+    }
+}
+
+impl<P> Sampling<P, rand::rngs::StdRng>
+where
+    P: PolicyAgent,
+{
+    pub fn seeded(agent: P, seed: u64) -> Self {
+        Self::new(agent, rand::SeedableRng::seed_from_u64(seed))
+    }
+}
+
+impl<P, R> Agent for Sampling<P, R>
+where
+    P: PolicyAgent + Send,
+    R: rand::Rng + Send,
+{
+    type Game = P::Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        let distribution = self.agent.policy(info, possible_moves);
+        assert!(!distribution.is_empty(), "分布が空");
+        let mut x: f64 = self.rng.gen();
+        let last = distribution.len() - 1;
+        for (i, (m, p)) in distribution.into_iter().enumerate() {
+            if x < p || i == last {
+                return m;
+            }
+            x -= p;
+        }
+        unreachable!()
+    }
 }
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{abstract_game::ImperfectInfoGame, defs::*};
+
+// 配り方を後から確かめるための commit-reveal。
+// ゲームを作るときに (DEAL_VERSION, config, seed, salt) の SHA-256 を公開しておき、
+// 決着したら seed と salt と配り方を公開する。参加者は
+// - ハッシュが最初に公開されたものと一致すること
+// - seed から gen_random した配り方が公開された配り方と一致すること
+// - その配り方で履歴の手を打ち直すと、同じ答えが返ること
+// を確かめれば、途中で配り方が差し替えられていないとわかる。
+// salt は seed を総当たりでハッシュから当てられないようにするためのもの。
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commitment {
+    pub deal_version: u32,
+    // 16 進の SHA-256
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reveal {
+    pub deal_version: u32,
+    pub config: GameConfig,
+    pub seed: u64,
+    pub salt: String,
+    pub distr: Distr,
+    pub history: Vec<MoveAns>,
+}
+
+// ハッシュする中身。フィールドの順に JSON にする
+#[derive(Serialize)]
+struct Payload<'a> {
+    deal_version: u32,
+    config: &'a GameConfig,
+    seed: u64,
+    salt: &'a str,
+}
+
+pub fn commit(config: &GameConfig, seed: u64, salt: &str) -> Commitment {
+    commit_with_version(DEAL_VERSION, config, seed, salt)
+}
+
+fn commit_with_version(
+    deal_version: u32,
+    config: &GameConfig,
+    seed: u64,
+    salt: &str,
+) -> Commitment {
+    let payload = Payload {
+        deal_version,
+        config,
+        seed,
+        salt,
+    };
+    let json = serde_json::to_vec(&payload).expect("failed to serialize commitment payload");
+    let hash = Sha256::digest(json)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Commitment { deal_version, hash }
+}
+
+// reveal が commitment と合っていて、履歴がその配り方で打ち直せることを確かめる
+pub fn verify_reveal(commitment: &Commitment, reveal: &Reveal) -> Result<(), String> {
+    if reveal.deal_version != commitment.deal_version {
+        return Err(format!(
+            "deal version {} does not match the commitment ({})",
+            reveal.deal_version, commitment.deal_version
+        ));
+    }
+    let recomputed = commit_with_version(
+        reveal.deal_version,
+        &reveal.config,
+        reveal.seed,
+        &reveal.salt,
+    );
+    if recomputed.hash != commitment.hash {
+        return Err("seed and salt do not match the commitment".to_string());
+    }
+    if reveal.deal_version != DEAL_VERSION {
+        return Err(format!(
+            "deal version {} can not be regenerated by this build (version {DEAL_VERSION})",
+            reveal.deal_version
+        ));
+    }
+
+    let mut game = reveal.config.gen_random(reveal.seed as usize);
+    if game.distr() != reveal.distr {
+        return Err("revealed distr is not the deal of the seed".to_string());
+    }
+    for (turn, move_ans) in reveal.history.iter().enumerate() {
+        if !game.move_game(move_ans.move_of_this()) {
+            return Err(format!("move at turn {turn} is not legal in the deal"));
+        }
+        if game.history().last() != Some(move_ans) {
+            return Err(format!("answer at turn {turn} does not match the deal"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::three_small;
+
+    use super::*;
+
+    #[test]
+    fn reveal_verifies_only_against_its_commitment() {
+        let config = three_small();
+        let commitment = commit(&config, 7, "salt");
+        let mut game = config.gen_random(7);
+        let (_, moves) = game.info_and_move_now();
+        game.move_game(moves[0].clone());
+        let reveal = Reveal {
+            deal_version: DEAL_VERSION,
+            config: config.clone(),
+            seed: 7,
+            salt: "salt".to_string(),
+            distr: game.distr(),
+            history: game.history(),
+        };
+        assert_eq!(verify_reveal(&commitment, &reveal), Ok(()));
+
+        let other_seed = Reveal {
+            seed: 8,
+            ..reveal.clone()
+        };
+        assert!(verify_reveal(&commitment, &other_seed).is_err());
+        let other_salt = commit(&config, 7, "pepper");
+        assert!(verify_reveal(&other_salt, &reveal).is_err());
+        // 答えを書き換えた履歴は通らない
+        let mut tampered = reveal.clone();
+        if let Some(MoveAns::Query { ans, .. }) = tampered.history.first_mut() {
+            *ans += 1;
+        }
+        assert!(verify_reveal(&commitment, &tampered).is_err());
+    }
+}
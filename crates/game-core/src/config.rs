@@ -86,6 +86,15 @@ pub fn four_midium() -> GameConfig {
     .unwrap()
 }
 
+pub fn by_name(name: &str) -> Option<GameConfig> {
+    match name {
+        "three_small" => Some(three_small()),
+        "three_midium" => Some(three_midium()),
+        "four_midium" => Some(four_midium()),
+        _ => None,
+    }
+}
+
 pub fn from_sorts_and_cards(
     sorts: impl IntoIterator<Item = &'static str>,
     cards_sort: impl IntoIterator<Item = impl IntoIterator<Item = &'static str>>,
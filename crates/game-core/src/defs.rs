@@ -73,6 +73,17 @@ impl GameConfig {
         }
         &self.cards_sort[*card]
     }
+    // 人数だけを変えた config。カードが足りなければ None。
+    // JSON から読んだ config は new を通っていないので、同じ人数でこれを呼んで確かめる。
+    pub fn with_player_num(&self, player_num: usize) -> Option<Self> {
+        Self::new(
+            self.sorts.clone(),
+            self.cards_sort.clone(),
+            player_num,
+            self.head_num,
+            self.hand_num,
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -101,6 +112,9 @@ impl View {
     }
 }
 
+// gen_random の配り方の版。同じ seed から違う配り方になるように変えたら上げる（commitment に入る）
+pub const DEAL_VERSION: u32 = 1;
+
 impl GameConfig {
     pub fn has_sort(&self, card: &Card, sort: &Sort) -> bool {
         self.cards_sort[*card].contains(sort)
@@ -134,9 +148,14 @@ impl GameConfig {
                 head: perm_consume(self.head_num),
             })
         }
+        self.game_from_distr(state)
+    }
+    // 配り方を指定して始める。 distr は config に合っていること
+    pub fn game_from_distr(&self, distr: Distr) -> Game {
+        debug_assert_eq!(distr.len(), self.player_num);
         Game {
             config: self.clone(),
-            distr: state,
+            distr,
             query_answer: vec![],
         }
     }
@@ -334,6 +353,8 @@ impl Info {
 impl abstract_game::ImperfectInfoGame for Game {
     type Info = Info;
     type Move = Move;
+    type Config = GameConfig;
+    type MoveAns = MoveAns;
     fn player_number(&self) -> usize {
         self.config.player_num
     }
@@ -383,4 +404,10 @@ impl abstract_game::ImperfectInfoGame for Game {
         self.query_answer.push(qa);
         true
     }
+    fn config(&self) -> Self::Config {
+        self.config.clone()
+    }
+    fn history(&self) -> Vec<Self::MoveAns> {
+        self.query_answer.clone()
+    }
 }
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{abstract_game::Player, defs::*};
+
+// Info と候補の Move を、config ごとに長さが決まった Vec<f32> にする。学習や解析のための形。
+// プレイヤーは全部 view の持ち主から数えた相対番号 r = (p - 持ち主) mod player_num で表す。
+// sort は config.all_sort() の順（名前順）の番号、カードは Card の番号で表す。
+//
+// 並び（ENCODING_VERSION = 1、P = player_num、C = cards_num、S = sort の数、H = history_len）:
+// - turn     : 1           これまでの手数
+// - hand     : C           自分の手札の one-hot
+// - visible  : (P - 1) * C  相対番号 1..P の相手の見えているカードの one-hot
+// - history  : H * SLOT    新しい手から順に H 手分。足りない分は 0
+//   - SLOT = present 1, who P, kind 2 (query, declare), query_to P, sort S,
+//            query の答え 1, declare の当たり 1, declare したカード C
+// - move     : 2 + P + S + C  候補の手。kind 2, query_to P, sort S, declare するカード C
+// 並びを変えたら ENCODING_VERSION を上げること。
+
+pub const ENCODING_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub name: String,
+    pub offset: usize,
+    pub len: usize,
+}
+
+// 解析側で並びを確かめるための情報
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodingHeader {
+    pub version: u32,
+    pub config: GameConfig,
+    pub history_len: usize,
+    pub len: usize,
+    pub layout: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encoder {
+    config: GameConfig,
+    sorts: Vec<Sort>,
+    history_len: usize,
+}
+
+impl Encoder {
+    pub const FALSE_DECLARES: usize = 2;
+
+    // history_len 手より古い手は捨てる
+    pub fn new(config: GameConfig, history_len: usize) -> Self {
+        let sorts = config.all_sort().into_iter().collect();
+        Self {
+            config,
+            sorts,
+            history_len,
+        }
+    }
+
+    // 同じ query はできないので、query は 1 人 (player_num - 1) * sort の数までで全部入る。
+    // 外れた宣言は頭の並べ方の数だけできるので、1 人 FALSE_DECLARES 回までしか入れない。
+    // それより長い試合は古い手から捨てる。
+    pub fn long_history(config: GameConfig) -> Self {
+        let p = config.player_num();
+        let history_len = p * ((p - 1) * config.all_sort().len() + Self::FALSE_DECLARES);
+        Self::new(config, history_len)
+    }
+
+    fn slot_len(&self) -> usize {
+        let (p, c, s) = self.sizes();
+        1 + p + 2 + p + s + 1 + 1 + c
+    }
+
+    fn move_len(&self) -> usize {
+        let (p, c, s) = self.sizes();
+        2 + p + s + c
+    }
+
+    fn sizes(&self) -> (usize, usize, usize) {
+        (
+            self.config.player_num(),
+            self.config.cards_num(),
+            self.sorts.len(),
+        )
+    }
+
+    pub fn layout(&self) -> Vec<Segment> {
+        let (p, c, _) = self.sizes();
+        let lens = [
+            ("turn", 1),
+            ("hand", c),
+            ("visible", (p - 1) * c),
+            ("history", self.history_len * self.slot_len()),
+            ("move", self.move_len()),
+        ];
+        let mut offset = 0;
+        lens.into_iter()
+            .map(|(name, len)| {
+                let segment = Segment {
+                    name: name.to_string(),
+                    offset,
+                    len,
+                };
+                offset += len;
+                segment
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.layout().iter().map(|segment| segment.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn header(&self) -> EncodingHeader {
+        EncodingHeader {
+            version: ENCODING_VERSION,
+            config: self.config.clone(),
+            history_len: self.history_len,
+            len: self.len(),
+            layout: self.layout(),
+        }
+    }
+
+    pub fn encode(&self, info: &Info, m: &Move) -> Vec<f32> {
+        assert_eq!(info.config, self.config, "config が違う");
+        let (p, c, _) = self.sizes();
+        let me = info
+            .view
+            .other
+            .iter()
+            .position(|other| other.is_none())
+            .unwrap_or(info.player_turn());
+        let relative = |player: Player| (player + p - me) % p;
+
+        let mut out = Vec::with_capacity(self.len());
+        out.push(info.query_answer.len() as f32);
+        out.extend(self.one_hot_cards(info.view.hand.iter()));
+        let mut visible = vec![0_f32; (p - 1) * c];
+        for (player, cards) in info.view.other.iter().enumerate() {
+            let Some(cards) = cards else {
+                continue;
+            };
+            let r = relative(player);
+            for card in cards {
+                visible[(r - 1) * c + card] = 1_f32;
+            }
+        }
+        out.extend(visible);
+
+        for i in 0..self.history_len {
+            let Some(move_ans) = info.query_answer.iter().rev().nth(i) else {
+                out.extend(vec![0_f32; self.slot_len()]);
+                continue;
+            };
+            out.push(1_f32);
+            out.extend(one_hot(p, relative(move_ans.who_move())));
+            let (query_ans, declare_ans) = match move_ans {
+                MoveAns::Query { ans, .. } => (*ans as f32, 0_f32),
+                MoveAns::Declare { ans, .. } => (0_f32, if *ans { 1_f32 } else { 0_f32 }),
+            };
+            let encoded = self.encode_move(&move_ans.move_of_this(), &relative);
+            let (kind_and_query, declare) = encoded.split_at(2 + p + self.sorts.len());
+            out.extend(kind_and_query);
+            out.push(query_ans);
+            out.push(declare_ans);
+            out.extend(declare);
+        }
+
+        out.extend(self.encode_move(m, &relative));
+        debug_assert_eq!(out.len(), self.len());
+        out
+    }
+
+    fn encode_move(&self, m: &Move, relative: &impl Fn(Player) -> usize) -> Vec<f32> {
+        let (p, c, s) = self.sizes();
+        let mut out = Vec::with_capacity(self.move_len());
+        match m {
+            Move::Query {
+                query_to,
+                query_sort,
+            } => {
+                out.extend([1_f32, 0_f32]);
+                out.extend(one_hot(p, relative(*query_to)));
+                let sort = self.sorts.iter().position(|sort| sort == query_sort);
+                out.extend(sort.map_or(vec![0_f32; s], |sort| one_hot(s, sort)));
+                out.extend(vec![0_f32; c]);
+            }
+            Move::Declare { declare } => {
+                out.extend([0_f32, 1_f32]);
+                out.extend(vec![0_f32; p + s]);
+                out.extend(self.one_hot_cards(declare.iter()));
+            }
+        }
+        out
+    }
+
+    fn one_hot_cards<'a>(&self, cards: impl Iterator<Item = &'a Card>) -> Vec<f32> {
+        let mut out = vec![0_f32; self.config.cards_num()];
+        for card in cards {
+            out[*card] = 1_f32;
+        }
+        out
+    }
+}
+
+fn one_hot(len: usize, i: usize) -> Vec<f32> {
+    let mut out = vec![0_f32; len];
+    out[i] = 1_f32;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{abstract_game::ImperfectInfoGame, config::three_small};
+
+    use super::*;
+
+    #[test]
+    fn encoding_follows_layout() {
+        let config = three_small();
+        let encoder = Encoder::long_history(config.clone());
+        // 3 人・5 種類なら query は 3 * 2 * 5、外れた宣言は 3 * 2
+        assert_eq!(encoder.header().history_len, 36);
+        let mut game = config.gen_random(3);
+        let (_, possible_moves) = game.info_and_move_now();
+        let query = possible_moves
+            .iter()
+            .find(|m| matches!(m, Move::Query { .. }))
+            .unwrap()
+            .clone();
+        game.move_game(query.clone());
+
+        let (info, possible_moves) = game.info_and_move_now();
+        let layout = encoder.layout();
+        let segment = |name: &str| layout.iter().find(|s| s.name == name).unwrap().clone();
+        for m in &possible_moves {
+            let encoded = encoder.encode(&info, m);
+            assert_eq!(encoded.len(), encoder.len());
+            assert_eq!(encoded[0], 1_f32);
+            let hand = segment("hand");
+            let hand_num: f32 = encoded[hand.offset..hand.offset + hand.len].iter().sum();
+            assert_eq!(hand_num as usize, config.hand_num());
+            // 直前の手は 1 つ前のプレイヤー（相対番号 P - 1）の query
+            let history = segment("history");
+            let slot = &encoded[history.offset..];
+            assert_eq!(slot[0], 1_f32);
+            assert_eq!(slot[config.player_num()], 1_f32);
+            assert_eq!(slot[1 + config.player_num()], 1_f32);
+        }
+        assert_eq!(
+            segment("move").offset + segment("move").len,
+            encoder.header().len
+        );
+    }
+}
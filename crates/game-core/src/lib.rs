@@ -1,4 +1,7 @@
 pub mod abstract_game;
+pub mod commitment;
 pub mod config;
 pub mod defs;
+pub mod encoding;
+pub mod runner;
 pub mod utils;
@@ -0,0 +1,349 @@
+use std::{
+    collections::BTreeSet,
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::abstract_game::{Agent, ImperfectInfoGame, Player};
+
+// AI の呼び出しを失敗してもよいものとして扱う。
+// panic・合法でない手・時間切れを Fault として記録し、FaultPolicy に従って続ける。
+// time_limit があるときは別の thread で AI に聞き、期限までに返ってこなければ待たずに諦める。
+// 返ってこなかった AI は止められないので thread ごと手放し、その席の以降の手番も時間切れとして扱う。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultPolicy {
+    // その AI の負けとしてゲームを終える（誰も勝たない）
+    Forfeit,
+    // 合法手からランダムに打つ
+    Random,
+    // 同じ AI にもう一度聞く。retries 回続けて失敗したらランダムに打つ
+    Retry { retries: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    Panic(String),
+    IllegalMove,
+    Timeout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultRecord {
+    pub turn: usize,
+    pub player: Player,
+    pub fault: Fault,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunnerConfig {
+    // wasm では thread が使えないので None にしておくこと
+    pub time_limit: Option<Duration>,
+    pub policy: FaultPolicy,
+    // ランダムに打つときの乱数
+    pub seed: u64,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            time_limit: None,
+            policy: FaultPolicy::Random,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub winner: Vec<usize>,
+    pub forfeited: Option<Player>,
+    pub faults: Vec<FaultRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Runner {
+    config: RunnerConfig,
+    rng: StdRng,
+    faults: Vec<FaultRecord>,
+    // 時間切れで AI を手放した席
+    abandoned: BTreeSet<Player>,
+}
+
+impl Runner {
+    pub fn new(config: RunnerConfig) -> Self {
+        let rng = StdRng::seed_from_u64(config.seed);
+        Self {
+            config,
+            rng,
+            faults: vec![],
+            abandoned: BTreeSet::new(),
+        }
+    }
+
+    // 時間切れで AI を手放した席か。そうなら席の agent は Abandoned に差し替わっている
+    pub fn is_abandoned(&self, player: Player) -> bool {
+        self.abandoned.contains(&player)
+    }
+
+    pub fn faults(&self) -> &[FaultRecord] {
+        &self.faults
+    }
+
+    pub fn into_faults(self) -> Vec<FaultRecord> {
+        self.faults
+    }
+
+    // agent に手を聞く。失敗したら記録して FaultPolicy に従い、Forfeit なら None を返す。
+    // 時間切れになった agent は、返ってこない thread に渡したまま Abandoned に差し替わる。
+    pub fn choose<G>(
+        &mut self,
+        agent: &mut Box<dyn Agent<Game = G>>,
+        player: Player,
+        turn: usize,
+        info: G::Info,
+        possible_moves: Vec<G::Move>,
+    ) -> Option<G::Move>
+    where
+        G: ImperfectInfoGame + 'static,
+        G::Info: Clone + Send + 'static,
+        G::Move: Clone + PartialEq + Send + 'static,
+    {
+        let mut attempts = 0;
+        loop {
+            let result = if self.abandoned.contains(&player) {
+                Err(Fault::Timeout)
+            } else {
+                self.ask(agent, info.clone(), possible_moves.clone())
+            };
+            let fault = match result {
+                Ok(m) => return Some(m),
+                Err(fault) => fault,
+            };
+            if fault == Fault::Timeout && self.config.time_limit.is_some() {
+                self.abandoned.insert(player);
+            }
+            self.faults.push(FaultRecord {
+                turn,
+                player,
+                fault,
+            });
+            match self.config.policy {
+                FaultPolicy::Forfeit => return None,
+                FaultPolicy::Retry { retries } if attempts < retries => attempts += 1,
+                FaultPolicy::Random | FaultPolicy::Retry { .. } => {
+                    if possible_moves.is_empty() {
+                        return None;
+                    }
+                    let i = self.rng.gen_range(0..possible_moves.len());
+                    return Some(possible_moves[i].clone());
+                }
+            }
+        }
+    }
+
+    fn ask<G>(
+        &self,
+        agent: &mut Box<dyn Agent<Game = G>>,
+        info: G::Info,
+        possible_moves: Vec<G::Move>,
+    ) -> Result<G::Move, Fault>
+    where
+        G: ImperfectInfoGame + 'static,
+        G::Info: Send + 'static,
+        G::Move: Clone + PartialEq + Send + 'static,
+    {
+        let moves = possible_moves.clone();
+        let m = match self.config.time_limit {
+            Some(limit) => ask_with_deadline(agent, info, moves, limit)?,
+            None => panic::catch_unwind(AssertUnwindSafe(|| agent.use_info(info, moves)))
+                .map_err(|payload| Fault::Panic(panic_message(payload)))?,
+        };
+        if !possible_moves.contains(&m) {
+            return Err(Fault::IllegalMove);
+        }
+        Ok(m)
+    }
+
+    // auto_game の、AI が失敗してもゲームを続ける版
+    pub fn run_game<G>(
+        mut self,
+        mut game: G,
+        mut agents: Vec<Box<dyn Agent<Game = G>>>,
+    ) -> GameResult
+    where
+        G: ImperfectInfoGame + 'static,
+        G::Info: Clone + Send + 'static,
+        G::Move: Clone + PartialEq + Send + 'static,
+    {
+        assert_eq!(agents.len(), game.player_number());
+        let config = game.config();
+        for (seat, agent) in agents.iter_mut().enumerate() {
+            agent.on_game_start(&config, seat);
+        }
+        let mut turn = 0;
+        let mut forfeited = None;
+        while game.is_win().is_none() {
+            let p = game.player_turn();
+            let (info, possible_moves) = game.info_and_move_now();
+            let Some(m) = self.choose(&mut agents[p], p, turn, info, possible_moves) else {
+                forfeited = Some(p);
+                break;
+            };
+            game.move_game(m);
+            let last = game.history().pop().unwrap();
+            for agent in agents.iter_mut() {
+                agent.observe(&last);
+            }
+            turn += 1;
+        }
+        let winner = game
+            .is_win()
+            .unwrap_or_else(|| vec![0; game.player_number()]);
+        for agent in agents.iter_mut() {
+            agent.on_game_end(&winner);
+        }
+        GameResult {
+            winner,
+            forfeited,
+            faults: self.faults,
+        }
+    }
+}
+
+// agent を別の thread に渡して聞く。limit までに返ってきたら agent を戻し、
+// 返ってこなければ agent は thread に置いたまま、席には Abandoned を置く
+fn ask_with_deadline<G>(
+    agent: &mut Box<dyn Agent<Game = G>>,
+    info: G::Info,
+    possible_moves: Vec<G::Move>,
+    limit: Duration,
+) -> Result<G::Move, Fault>
+where
+    G: ImperfectInfoGame + 'static,
+    G::Info: Send + 'static,
+    G::Move: Send + 'static,
+{
+    let mut worker = mem::replace(agent, Box::new(Abandoned(PhantomData)));
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| worker.use_info(info, possible_moves)));
+        // 時間切れのあとなら受け取る側はもういない
+        let _ = sender.send((worker, result.map_err(panic_message)));
+    });
+    match receiver.recv_timeout(limit) {
+        Ok((worker, result)) => {
+            *agent = worker;
+            result.map_err(Fault::Panic)
+        }
+        Err(_) => Err(Fault::Timeout),
+    }
+}
+
+// 時間切れで手放した agent の代わり。Runner はこの席に手を聞かなくなる
+struct Abandoned<G>(PhantomData<fn() -> G>);
+
+impl<G: ImperfectInfoGame> Agent for Abandoned<G> {
+    type Game = G;
+    fn use_info(&mut self, _info: G::Info, _possible_moves: Vec<G::Move>) -> G::Move {
+        panic!("agent was abandoned after a timeout")
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::three_small, defs::*};
+
+    use super::*;
+
+    // 1 手目は panic し、そのあとは合法でない手を返す
+    struct Broken(usize);
+
+    impl Agent for Broken {
+        type Game = Game;
+        fn use_info(&mut self, _info: Info, _possible_moves: Vec<Move>) -> Move {
+            self.0 += 1;
+            if self.0 == 1 {
+                panic!("broken");
+            }
+            Move::Declare {
+                declare: Default::default(),
+            }
+        }
+    }
+
+    struct Hanging;
+
+    impl Agent for Hanging {
+        type Game = Game;
+        fn use_info(&mut self, _info: Info, _possible_moves: Vec<Move>) -> Move {
+            loop {
+                std::thread::park();
+            }
+        }
+    }
+
+    fn broken_agents(n: usize) -> Vec<Box<dyn Agent<Game = Game>>> {
+        (0..n)
+            .map(|_| Box::new(Broken(0)) as Box<dyn Agent<Game = Game>>)
+            .collect()
+    }
+
+    #[test]
+    fn faults_are_recorded_instead_of_crashing() {
+        let config = three_small();
+
+        let result = Runner::new(RunnerConfig {
+            policy: FaultPolicy::Forfeit,
+            ..Default::default()
+        })
+        .run_game(config.gen_random(0), broken_agents(3));
+        assert_eq!(result.forfeited, Some(0));
+        assert_eq!(result.winner, vec![0, 0, 0]);
+        assert_eq!(result.faults[0].fault, Fault::Panic("broken".to_string()));
+
+        let result = Runner::new(RunnerConfig {
+            policy: FaultPolicy::Retry { retries: 1 },
+            ..Default::default()
+        })
+        .run_game(config.gen_random(0), broken_agents(3));
+        assert_eq!(result.forfeited, None);
+        assert_eq!(result.winner.iter().sum::<usize>(), 1);
+        assert_eq!(result.faults[1].fault, Fault::IllegalMove);
+
+        // 返ってこない AI を待たずに、以降はランダムに打って進める
+        let mut agents = broken_agents(3);
+        agents[0] = Box::new(Hanging);
+        let result = Runner::new(RunnerConfig {
+            time_limit: Some(Duration::from_millis(50)),
+            ..Default::default()
+        })
+        .run_game(config.gen_random(0), agents);
+        assert_eq!(result.forfeited, None);
+        assert!(result
+            .faults
+            .iter()
+            .filter(|f| f.player == 0)
+            .all(|f| f.fault == Fault::Timeout));
+    }
+}
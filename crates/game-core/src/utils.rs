@@ -5,12 +5,31 @@ use crate::abstract_game::Player;
 use super::defs::*;
 use std::collections::{BTreeSet, HashMap, HashSet};
 
+pub mod declare;
+pub mod opponent;
+
+// 履歴と view に矛盾しない配置の全体。各配置はちょうど一度ずつ出てくる。
+// view は手番のプレイヤーのものとする。
 pub fn possible_states<'a>(
     config: &'a GameConfig,
     query_answer: &'a [MoveAns],
     view: &'a View,
 ) -> impl Iterator<Item = Distr> + 'a {
-    let player = config.player_turn(query_answer.len());
+    possible_states_of(
+        config,
+        query_answer,
+        view,
+        config.player_turn(query_answer.len()),
+    )
+}
+
+// possible_states の、手番でないプレイヤーの view でも使える版。
+pub fn possible_states_of<'a>(
+    config: &'a GameConfig,
+    query_answer: &'a [MoveAns],
+    view: &'a View,
+    player: Player,
+) -> impl Iterator<Item = Distr> + 'a {
     let not_in_view: Vec<Card> = config
         .all_cards()
         .into_iter()
@@ -24,37 +43,51 @@ pub fn possible_states<'a>(
         })
         .collect();
 
-    let n = not_in_view.len();
     let all_player = 0..config.player_num();
     let (head_num, hand_num) = (config.head_num(), config.hand_num());
 
-    not_in_view
+    // 見えていないカードを、自分の頭と他のプレイヤーの手札に順に割り振る。
+    // 組み合わせで選ぶので、同じ配置は一度だけ出てくる。
+    let mut assigns: Vec<(Vec<BTreeSet<Card>>, Vec<Card>)> = vec![(vec![], not_in_view)];
+    for p in all_player.clone() {
+        let k = if p == player { head_num } else { hand_num };
+        assigns = assigns
+            .into_iter()
+            .flat_map(|(assigned, rest)| {
+                rest.clone().into_iter().combinations(k).map(move |chosen| {
+                    let mut assigned = assigned.clone();
+                    let rest = rest
+                        .iter()
+                        .filter(|c| !chosen.contains(c))
+                        .cloned()
+                        .collect();
+                    assigned.push(chosen.into_iter().collect());
+                    (assigned, rest)
+                })
+            })
+            .collect();
+    }
+
+    assigns
         .into_iter()
-        .permutations(n)
-        .map(move |not_in_view| {
-            let mut state = vec![];
-            let mut perm = not_in_view.into_iter();
-
-            let mut perm_consume = |i: usize| -> BTreeSet<Card> {
-                let mut v = BTreeSet::new();
-                for _ in 0..i {
-                    v.insert(perm.next().unwrap());
-                }
-                v
-            };
-
-            for p in all_player.clone() {
-                let (head, hand) = if p == player {
-                    (perm_consume(head_num), view.hand.clone())
-                } else {
-                    (
-                        view.other[p].as_ref().unwrap().clone(),
-                        perm_consume(hand_num),
-                    )
-                };
-                state.push(PlCard { head, hand })
-            }
-            state
+        .map(move |(assigned, _)| {
+            all_player
+                .clone()
+                .zip(assigned)
+                .map(|(p, cards)| {
+                    if p == player {
+                        PlCard {
+                            head: cards,
+                            hand: view.hand.clone(),
+                        }
+                    } else {
+                        PlCard {
+                            head: view.other[p].as_ref().unwrap().clone(),
+                            hand: cards,
+                        }
+                    }
+                })
+                .collect::<Distr>()
         })
         .filter(move |distr| {
             query_answer.iter().all(|qa| {
@@ -111,9 +144,7 @@ pub fn possible_head_numed(
     let mut maps = HashMap::new();
     for distr in possible_distr {
         let head = players_head(&distr, player);
-        maps.entry(head.clone())
-            .and_modify(|v| *v += 1)
-            .or_insert(0);
+        *maps.entry(head.clone()).or_insert(0) += 1;
     }
     maps
 }
@@ -145,6 +176,22 @@ pub fn answerable_info(
     answerable(config, query_answer, view)
 }
 
+// 情報集合で考える意味のある手。
+// 頭が確定しているならその宣言だけ、そうでなければ質問と、ありうる頭の宣言。
+pub fn reasonable_moves(config: &GameConfig, query_answer: &[MoveAns], view: &View) -> Vec<Move> {
+    let player = config.player_turn(query_answer.len());
+    let heads: BTreeSet<_> = possible_states(config, query_answer, view)
+        .map(|distr| players_head(&distr, player).clone())
+        .collect();
+    if heads.len() == 1 {
+        let declare = heads.into_iter().next().unwrap();
+        return vec![Move::Declare { declare }];
+    }
+    movable_query_ref(config, query_answer, player)
+        .chain(heads.into_iter().map(|declare| Move::Declare { declare }))
+        .collect()
+}
+
 pub fn random_vec<R, T>(rng: &mut R, v: Vec<T>) -> T
 where
     R: rand::Rng,
@@ -161,6 +208,10 @@ where
     G: crate::abstract_game::ImperfectInfoGame,
 {
     assert_eq!(agents.len(), game.player_number());
+    let config = game.config();
+    for (seat, agent) in agents.iter_mut().enumerate() {
+        agent.on_game_start(&config, seat);
+    }
     while game.is_win().is_none() {
         let p = game.player_turn();
         let info_move = game.info_and_move_now();
@@ -168,14 +219,22 @@ where
         if !game.move_game(m) {
             panic!("動けるやつにして");
         }
+        let last = game.history().pop().unwrap();
+        for agent in agents.iter_mut() {
+            agent.observe(&last);
+        }
     }
-    game.is_win().unwrap()
+    let outcome = game.is_win().unwrap();
+    for agent in agents.iter_mut() {
+        agent.on_game_end(&outcome);
+    }
+    outcome
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::three_small;
     use crate::abstract_game::ImperfectInfoGame;
+    use crate::config::three_small;
 
     use super::*;
     #[test]
@@ -209,4 +268,62 @@ mod tests {
         // let info = game.info_and_move_now();
         // eprintln!("{info:?}");
     }
+
+    // 最初の合法手を打ち、フックが呼ばれた回数を (start, observe, end) で数える。
+    // auto_game は Box を受け取るので、数えた結果は Arc で共有して取り出す。
+    struct Counting(std::sync::Arc<std::sync::Mutex<(usize, usize, usize)>>);
+
+    impl crate::abstract_game::Agent for Counting {
+        type Game = Game;
+        fn use_info(&mut self, _info: Info, possible_moves: Vec<Move>) -> Move {
+            possible_moves.into_iter().next().unwrap()
+        }
+        fn on_game_start(&mut self, _config: &GameConfig, _seat: Player) {
+            self.0.lock().unwrap().0 += 1;
+        }
+        fn observe(&mut self, _move_ans: &MoveAns) {
+            self.0.lock().unwrap().1 += 1;
+        }
+        fn on_game_end(&mut self, _outcome: &[usize]) {
+            self.0.lock().unwrap().2 += 1;
+        }
+    }
+
+    #[test]
+    fn auto_game_calls_hooks() {
+        let config = three_small();
+        let counts: Vec<_> = (0..config.player_num())
+            .map(|_| std::sync::Arc::new(std::sync::Mutex::new((0, 0, 0))))
+            .collect();
+        let agents: Vec<Box<dyn crate::abstract_game::Agent<Game = Game>>> = counts
+            .iter()
+            .map(|c| Box::new(Counting(c.clone())) as Box<_>)
+            .collect();
+        auto_game(config.gen_random(0), agents);
+
+        // 同じ手順をもう一度なぞって手数を数える
+        let mut game = config.gen_random(0);
+        while game.is_win().is_none() {
+            let (_, moves) = game.info_and_move_now();
+            game.move_game(moves.into_iter().next().unwrap());
+        }
+        for c in &counts {
+            assert_eq!(*c.lock().unwrap(), (1, game.history().len(), 1));
+        }
+    }
+
+    #[test]
+    fn possible_states_are_distinct_and_contain_truth() {
+        let config = crate::config::three_midium();
+        let game = config.gen_random(0);
+        let info = game.info_and_move_now().0;
+        let states: Vec<_> =
+            possible_states(&info.config, &info.query_answer, &info.view).collect();
+        // 見えない 6 枚を 頭 2 枚・手札 2 枚・手札 2 枚 に分ける
+        assert_eq!(states.len(), 90);
+        for (i, s) in states.iter().enumerate() {
+            assert!(!states[i + 1..].contains(s));
+        }
+        assert!(states.contains(&game.distr()));
+    }
 }
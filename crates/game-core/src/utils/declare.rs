@@ -0,0 +1,296 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::{movable_query_ref, opponent::opponent_knowledge, possible_states};
+use crate::{
+    abstract_game::{Agent, Candidate, Decision, ImperfectInfoGame, Player, Rationale},
+    defs::*,
+};
+
+// 確定していなくても宣言するかどうかを決める方針。
+// - 今宣言したときに当たる確率 p : 一番多い頭の割合
+// - 待ったときに勝てる見込み : 自分が確定するまでの手番の数だけ、相手が誰も先に当てない確率
+// を比べて、p + risk が待つ見込み以上なら宣言する。
+// 自分が確定するまでの手番の数は、候補の頭の数が 1 回の質問で半分になるとして見積もる。
+// 情報が得られる質問の残りがそれより少なければ、待っても確定しないので宣言する。
+// 相手が当てる確率は今の時点で確定している確率で見積もるので、待つ見込みは楽観的になる。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeclarePolicy {
+    // 正ならより早く、負ならより遅く宣言する
+    pub risk: f64,
+}
+
+impl Default for DeclarePolicy {
+    fn default() -> Self {
+        Self { risk: 0_f64 }
+    }
+}
+
+impl DeclarePolicy {
+    pub fn new(risk: f64) -> Self {
+        Self { risk }
+    }
+
+    // 宣言するならその手を返す。確定しているときは必ず宣言する。
+    pub fn decide(&self, info: &Info) -> Option<Move> {
+        let evaluation = self.evaluate(info)?;
+        evaluation.declare.then_some(evaluation.best)
+    }
+
+    // decide の判断の中身。宣言できる頭が残っていなければ None。
+    pub fn evaluate(&self, info: &Info) -> Option<DeclareEvaluation> {
+        let player = info.player_turn();
+        let states: Vec<Distr> =
+            possible_states(&info.config, &info.query_answer, &info.view).collect();
+        let movable_declare = info.movable_declare();
+        let mut heads: HashMap<Move, usize> = HashMap::new();
+        for distr in &states {
+            let head = Move::Declare {
+                declare: players_head(distr, player).clone(),
+            };
+            *heads.entry(head).or_insert(0) += 1;
+        }
+        let head_kinds = heads.len();
+        let mut heads: Vec<(Move, f64)> = heads
+            .into_iter()
+            .filter(|(m, _)| movable_declare.contains(m))
+            .map(|(m, num)| (m, num as f64 / states.len() as f64))
+            .collect();
+        heads.sort_by(|(m1, p1), (m2, p2)| p2.partial_cmp(p1).unwrap().then(m1.cmp(m2)));
+        let (best, p) = heads.first()?.clone();
+        if head_kinds == 1 {
+            return Some(DeclareEvaluation {
+                best,
+                declare: true,
+                p,
+                wait: 0_f64,
+                risk: self.risk,
+                worlds: states.len(),
+                heads,
+            });
+        }
+
+        let no_one_wins: f64 = opponent_knowledge(info)
+            .iter()
+            .map(|knowledge| 1_f64 - knowledge.certain)
+            .product();
+        let rounds = (head_kinds as f64).log2().ceil().max(1_f64) as usize;
+        let wait = wait_estimate(no_one_wins, rounds, informative_queries(info, &states));
+
+        Some(DeclareEvaluation {
+            best,
+            declare: p + self.risk >= wait,
+            p,
+            wait,
+            risk: self.risk,
+            worlds: states.len(),
+            heads,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeclareEvaluation {
+    // 一番ありそうな頭の宣言
+    pub best: Move,
+    pub declare: bool,
+    pub p: f64,
+    pub wait: f64,
+    pub risk: f64,
+    pub worlds: usize,
+    // 宣言できる頭ごとの割合。多い順。
+    pub heads: Vec<(Move, f64)>,
+}
+
+impl DeclareEvaluation {
+    pub fn decision(&self) -> Decision {
+        Decision {
+            declare: self.declare,
+            values: vec![
+                ("p".to_string(), self.p),
+                ("wait".to_string(), self.wait),
+                ("risk".to_string(), self.risk),
+            ],
+        }
+    }
+
+    pub fn rationale(&self) -> Rationale<Move> {
+        Rationale {
+            worlds: Some(self.worlds),
+            candidates: self
+                .heads
+                .iter()
+                .map(|(m, p)| Candidate {
+                    kind: "head_frequency".to_string(),
+                    action: m.clone(),
+                    value: *p,
+                })
+                .collect(),
+            decision: Some(self.decision()),
+        }
+    }
+}
+
+// 待ったときに勝てる見込み。確定までの rounds 手番のあいだ、毎回 no_one_wins の確率で誰も当てないとする。
+// 情報が得られる質問の残り remaining が rounds より少なければ、待っても確定しないので 0
+fn wait_estimate(no_one_wins: f64, rounds: usize, remaining: usize) -> f64 {
+    if remaining >= rounds {
+        no_one_wins.powi(rounds as i32)
+    } else {
+        0_f64
+    }
+}
+
+// 可能な配置の中で答えが分かれる、まだ聞いていない質問の数
+fn informative_queries(info: &Info, states: &[Distr]) -> usize {
+    let player = info.player_turn();
+    movable_query_ref(&info.config, &info.query_answer, player)
+        .filter(|q| {
+            let answers: BTreeSet<_> = states
+                .iter()
+                .map(|distr| answer(&info.config, distr, q.clone(), player))
+                .collect();
+            answers.len() > 1
+        })
+        .count()
+}
+
+// 他の AI に DeclarePolicy を足す。宣言すると決めたときだけ宣言し、それ以外は元の AI に任せる。
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithDeclarePolicy<A> {
+    agent: A,
+    policy: DeclarePolicy,
+    last: Option<Rationale<Move>>,
+}
+
+impl<A> WithDeclarePolicy<A> {
+    pub fn new(agent: A, policy: DeclarePolicy) -> Self {
+        Self {
+            agent,
+            policy,
+            last: None,
+        }
+    }
+}
+
+impl<A> Agent for WithDeclarePolicy<A>
+where
+    A: Agent<Game = Game>,
+{
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        let evaluation = self.policy.evaluate(&info);
+        if let Some(evaluation) = &evaluation {
+            if evaluation.declare {
+                self.last = Some(evaluation.rationale());
+                return evaluation.best.clone();
+            }
+        }
+        let m = self.agent.use_info(info, possible_moves);
+        // 元の AI の理由に、宣言しなかった判断を足す
+        self.last = match (self.agent.rationale(), evaluation) {
+            (Some(rationale), Some(evaluation)) => Some(Rationale {
+                decision: Some(evaluation.decision()),
+                ..rationale
+            }),
+            (None, Some(evaluation)) => Some(evaluation.rationale()),
+            (rationale, None) => rationale,
+        };
+        m
+    }
+
+    fn rationale(&self) -> Option<Rationale<Move>> {
+        self.last.clone()
+    }
+
+    fn on_game_start(&mut self, config: &GameConfig, seat: Player) {
+        self.agent.on_game_start(config, seat);
+    }
+
+    fn observe(&mut self, move_ans: &MoveAns) {
+        self.agent.observe(move_ans);
+    }
+
+    fn on_game_end(&mut self, outcome: &[usize]) {
+        self.agent.on_game_end(outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{abstract_game::ImperfectInfoGame, config::three_small};
+
+    use super::*;
+
+    #[test]
+    fn risk_moves_the_declare_point() {
+        let config = three_small();
+        let game = config.gen_random(0);
+        let (info, _) = game.info_and_move_now();
+        // 何でも宣言する / 確定するまで宣言しない
+        assert!(DeclarePolicy::new(1_f64).decide(&info).is_some());
+        assert!(DeclarePolicy::new(-1_f64).decide(&info).is_none());
+
+        // 同じ p でも、確定までの手番が多いほど相手に先を越されやすくなり、宣言するようになる
+        let p = 0.4;
+        let risk = DeclarePolicy::default().risk;
+        let declares = |rounds, remaining| p + risk >= wait_estimate(0.8, rounds, remaining);
+        assert!(!declares(1, 10));
+        assert!(!declares(3, 10));
+        assert!(declares(5, 10));
+        // 情報が得られる質問が足りなければ、待っても確定しないので宣言する
+        assert!(declares(3, 2));
+    }
+
+    // 先頭の手を打ち、それを理由として返す
+    struct First;
+
+    impl Agent for First {
+        type Game = Game;
+        fn use_info(&mut self, _info: Info, possible_moves: Vec<Move>) -> Move {
+            possible_moves[0].clone()
+        }
+        fn rationale(&self) -> Option<Rationale<Move>> {
+            Some(Rationale {
+                candidates: vec![Candidate {
+                    kind: "first".to_string(),
+                    action: Move::Declare {
+                        declare: BTreeSet::new(),
+                    },
+                    value: 1_f64,
+                }],
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn with_declare_policy_declares_or_defers() {
+        let game = three_small().gen_random(0);
+        let (info, moves) = game.info_and_move_now();
+
+        // evaluate が宣言すると言えば、その手と理由
+        let policy = DeclarePolicy::new(1_f64);
+        let evaluation = policy.evaluate(&info).unwrap();
+        assert!(evaluation.declare);
+        let mut agent = WithDeclarePolicy::new(First, policy);
+        assert_eq!(agent.use_info(info.clone(), moves.clone()), evaluation.best);
+        assert_eq!(agent.rationale(), Some(evaluation.rationale()));
+
+        // そうでなければ元の AI の手で、理由は元の AI のものに宣言しなかった判断を足したもの
+        let policy = DeclarePolicy::new(-1_f64);
+        let evaluation = policy.evaluate(&info).unwrap();
+        assert!(!evaluation.declare);
+        let mut agent = WithDeclarePolicy::new(First, policy);
+        assert_eq!(agent.use_info(info, moves.clone()), moves[0]);
+        let rationale = agent.rationale().unwrap();
+        assert_eq!(rationale.candidates, First.rationale().unwrap().candidates);
+        assert_eq!(rationale.decision, Some(evaluation.decision()));
+    }
+}
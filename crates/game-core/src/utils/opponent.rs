@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::possible_states_of;
+use crate::{abstract_game::Player, defs::*};
+
+// 相手の知識のモデル。
+// 自分から見て可能な世界それぞれで相手の view を作り、
+// 相手から見て可能な世界の数と、相手の頭が確定しているかを数える。
+// 相手の頭は自分に見えていて、相手の手札は自分の見えないカードのどれかなので、相手の view はほとんど推測できる。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpponentKnowledge {
+    pub player: Player,
+    // 相手から見て可能な世界の数 -> その確率
+    pub worlds_num: BTreeMap<usize, f64>,
+    // 相手がもう確実に宣言できる確率
+    pub certain: f64,
+}
+
+// info.view の持ち主から見た、各相手の知識。
+pub fn opponent_knowledge(info: &Info) -> Vec<OpponentKnowledge> {
+    let config = &info.config;
+    let me = view_owner(&info.view).unwrap_or(info.player_turn());
+    let worlds: Vec<Distr> =
+        possible_states_of(config, &info.query_answer, &info.view, me).collect();
+    let weight = 1_f64 / worlds.len().max(1) as f64;
+
+    (0..config.player_num())
+        .filter(|player| *player != me)
+        .map(|player| {
+            let mut worlds_num = BTreeMap::new();
+            let mut certain = 0_f64;
+            for world in &worlds {
+                let (num, is_certain) =
+                    knowledge_in_world(config, &info.query_answer, world, player);
+                *worlds_num.entry(num).or_insert(0_f64) += weight;
+                if is_certain {
+                    certain += weight;
+                }
+            }
+            OpponentKnowledge {
+                player,
+                worlds_num,
+                certain,
+            }
+        })
+        .collect()
+}
+
+// world が本当の配置だったときの、player から見て可能な世界の数と、 player の頭が確定しているか。
+pub fn knowledge_in_world(
+    config: &GameConfig,
+    query_answer: &[MoveAns],
+    world: &Distr,
+    player: Player,
+) -> (usize, bool) {
+    let view = cards_from_player(world, player);
+    let mut num = 0;
+    let mut certain = true;
+    for distr in possible_states_of(config, query_answer, &view, player) {
+        num += 1;
+        if players_head(&distr, player) != players_head(world, player) {
+            certain = false;
+        }
+    }
+    (num, certain)
+}
+
+// View は持ち主のところだけ other が None になっている
+fn view_owner(view: &View) -> Option<Player> {
+    view.other.iter().position(|other| other.is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{abstract_game::ImperfectInfoGame, config::three_small};
+
+    use super::*;
+
+    #[test]
+    fn opponent_knowledge_is_a_distribution() {
+        let config = three_small();
+        let mut game = config.gen_random(0);
+        let (info, _) = game.info_and_move_now();
+        let knowledge = opponent_knowledge(&info);
+        assert_eq!(knowledge.len(), 2);
+        for k in &knowledge {
+            let total: f64 = k.worlds_num.values().sum();
+            assert!((total - 1_f64).abs() < 1e-9);
+            assert!((0_f64..=1_f64).contains(&k.certain));
+        }
+
+        // 手番が進むと、見ている人も次のプレイヤーに変わる
+        let (_, moves) = game.info_and_move_now();
+        game.move_game(
+            moves
+                .into_iter()
+                .find(|m| matches!(m, Move::Query { .. }))
+                .unwrap(),
+        );
+        let (info, _) = game.info_and_move_now();
+        let knowledge = opponent_knowledge(&info);
+        assert!(knowledge.iter().all(|k| k.player != 1));
+    }
+}
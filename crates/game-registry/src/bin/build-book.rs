@@ -0,0 +1,111 @@
+use game_artifact::{ArtifactKey, ArtifactStore, OpeningBook};
+use game_core::config;
+use game_registry::{AgentSpec, BuildContext};
+
+// 本を引ける AI（entropy / search）の序盤の本を作って artifact store に保存する。
+// 本の中身は、本を使わない同じ AI が選ぶ手。
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(message) => usage_and_exit(&message),
+    };
+
+    let (agent, version) = args
+        .spec
+        .book()
+        .unwrap_or_else(|| usage_and_exit(&format!("{} has no opening book", args.spec)));
+    if args
+        .spec
+        .params()
+        .is_some_and(|params| params.usize("book") == 1)
+    {
+        usage_and_exit("build the book with book=0");
+    }
+    let config = config::by_name(&args.config)
+        .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {}", args.config)));
+    let mut player = args
+        .spec
+        .build(&BuildContext::default())
+        .unwrap_or_else(|err| usage_and_exit(&err));
+
+    let book = OpeningBook::build(&config, args.turns, |info, possible_moves| {
+        player.use_info(info, possible_moves)
+    });
+    let store = match &args.store {
+        Some(root) => ArtifactStore::new(root),
+        None => ArtifactStore::from_env(),
+    };
+    let key = ArtifactKey::new(&agent, version, &config, OpeningBook::ARTIFACT_NAME);
+    store
+        .save(&key, &book)
+        .expect("failed to save opening book");
+    eprintln!(
+        "saved {} positions to {}",
+        book.table.len(),
+        store.path(&key).display()
+    );
+}
+
+struct Args {
+    spec: AgentSpec,
+    config: String,
+    turns: usize,
+    store: Option<String>,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut spec = None;
+    let mut parsed_config = "three_midium".to_string();
+    let mut turns = 1;
+    let mut store = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--config" => {
+                i += 1;
+                parsed_config = parse_string(args.get(i), "--config")?;
+            }
+            "--turns" => {
+                i += 1;
+                turns = parse_usize(args.get(i), "--turns")?;
+            }
+            "--store" => {
+                i += 1;
+                store = Some(parse_string(args.get(i), "--store")?);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown argument: {flag}")),
+            value if spec.is_none() => spec = Some(AgentSpec::parse(value)?),
+            value => return Err(format!("unexpected argument: {value}")),
+        }
+        i += 1;
+    }
+
+    Ok(Args {
+        spec: spec.ok_or("missing SPEC")?,
+        config: parsed_config,
+        turns,
+        store,
+    })
+}
+
+fn parse_string(value: Option<&String>, flag: &str) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("missing value for {flag}"))
+}
+
+fn parse_usize(value: Option<&String>, flag: &str) -> Result<usize, String> {
+    value
+        .ok_or_else(|| format!("missing value for {flag}"))?
+        .parse::<usize>()
+        .map_err(|_| format!("invalid integer for {flag}"))
+}
+
+fn usage_and_exit(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: build-book SPEC [--config NAME] [--turns N] [--store DIR]");
+    eprintln!("example: cargo run --release -p game-registry --bin build-book -- entropy --config three_midium --turns 1");
+    std::process::exit(2);
+}
@@ -0,0 +1,746 @@
+#[cfg(feature = "native")]
+use std::time::Duration;
+use std::{collections::BTreeMap, fmt};
+
+use game_ai_bayes::{entropy_model, BayesPlayer};
+#[cfg(feature = "native")]
+use game_ai_cfr::{CfrPlayer, CfrPolicy};
+use game_ai_cheater::{CheaterMode, CheaterPlayer};
+use game_ai_entropy::UseEntropyPlayer;
+#[cfg(feature = "native")]
+use game_ai_external::{ExternalOptions, ExternalPlayer};
+use game_ai_ismcts::{IsmctsConfig, IsmctsPlayer};
+#[cfg(feature = "native")]
+use game_ai_learned::LearnedPlayer;
+use game_ai_meta::{MetaMode, MetaPlayer, MetaSpec};
+use game_ai_random::RandomPlayer;
+use game_ai_search::SearchPlayer;
+use game_ai_unfair::Unfair;
+use game_artifact::ArtifactStore;
+use game_core::{
+    abstract_game::Agent,
+    defs::{Distr, Game},
+    utils::declare::{DeclarePolicy, WithDeclarePolicy},
+};
+use rand::{rngs::SmallRng, SeedableRng};
+
+// AI の作り方をまとめておく。game-stats / cli / web はここから AI を作るので、
+// 新しい AI はここの ENTRIES に足せばどこからでも使える。
+// プロセスやファイル、時刻を使うもの（learned / cfr / external）は "native" feature のときだけ入る。web では外す。
+// spec は "search" や "search:depth=3,samples=0" の形で、":" の後ろは Entry の params。
+// "meta:..." だけは特別で、":" の後ろは MetaSpec の形（中の AI も spec で、params をつけるなら [] で囲む）。
+
+pub type BoxedAgent = Box<dyn Agent<Game = Game>>;
+
+pub struct Entry {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub params: &'static [Param],
+    // 本当の配置がないと作れない
+    pub needs_oracle: bool,
+    build: fn(&Params, &BuildContext) -> Result<BoxedAgent, String>,
+}
+
+pub struct Param {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: ParamKind,
+    // None なら省略できるが、既定値はない（seed など）
+    pub default: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    Int { min: u64, max: u64 },
+    Float { min: f64, max: f64 },
+    // ファイルのパスなど。"," は入れられない
+    Text,
+}
+
+const SEED: Param = Param {
+    name: "seed",
+    help: "乱数の seed。省略すると BuildContext の seed を使い、それもなければ時刻から決める（native でなければ 0）",
+    kind: ParamKind::Int {
+        min: 0,
+        max: u64::MAX,
+    },
+    default: None,
+};
+
+const RISK: Param = Param {
+    name: "risk",
+    help: "渡すと、確定していなくても DeclarePolicy で宣言する（正ならより早く、負ならより遅く）。省略すると確定するまで宣言しない",
+    kind: ParamKind::Float {
+        min: -1_f64,
+        max: 1_f64,
+    },
+    default: None,
+};
+
+const BOOK: Param = Param {
+    name: "book",
+    help: "1 なら序盤は build-book で作った本を引く。本は GAME_ARTIFACTS（なければ ./artifacts）から読む",
+    kind: ParamKind::Int { min: 0, max: 1 },
+    default: Some(0_f64),
+};
+
+pub const ENTRIES: &[Entry] = &[
+    Entry {
+        name: "random",
+        help: "合法手からランダムに打つ",
+        params: &[SEED],
+        needs_oracle: false,
+        build: |params, context| {
+            let seed = params.seed(context).unwrap_or_else(time_seed);
+            Ok(Box::new(RandomPlayer::new(SmallRng::seed_from_u64(seed))))
+        },
+    },
+    Entry {
+        name: "entropy",
+        help: "答えのエントロピーが一番大きい query を打ち、確定したら宣言する",
+        params: &[BOOK, RISK],
+        needs_oracle: false,
+        build: |params, _| {
+            let player = match params.usize("book") {
+                1 => UseEntropyPlayer::with_book(ArtifactStore::from_env()),
+                _ => UseEntropyPlayer::default(),
+            };
+            Ok(with_risk(player, params))
+        },
+    },
+    Entry {
+        name: "search",
+        help: "可能な世界をサンプルして depth 手先まで探索する",
+        params: &[
+            Param {
+                name: "depth",
+                help: "探索する手数",
+                kind: ParamKind::Int { min: 1, max: 6 },
+                default: Some(2_f64),
+            },
+            Param {
+                name: "samples",
+                help: "サンプルする世界の数。0 なら全部",
+                kind: ParamKind::Int { min: 0, max: 1000 },
+                default: Some(SearchPlayer::DEFAULT_SAMPLES as f64),
+            },
+            BOOK,
+            RISK,
+        ],
+        needs_oracle: false,
+        build: |params, _| {
+            let player = SearchPlayer::with_samples(params.usize("depth"), params.usize("samples"));
+            let player = match params.usize("book") {
+                1 => player.with_book(ArtifactStore::from_env()),
+                _ => player,
+            };
+            Ok(with_risk(player, params))
+        },
+    },
+    Entry {
+        name: "unfair",
+        help: "確定していなくても、勝てる見込みが高ければ先に宣言する",
+        params: &[
+            Param {
+                name: "risk",
+                help: "正ならより早く、負ならより遅く宣言する。attack も省略すると 0",
+                kind: ParamKind::Float {
+                    min: -1_f64,
+                    max: 1_f64,
+                },
+                default: None,
+            },
+            Param {
+                name: "attack",
+                help: "以前の閾値での指定。risk = 1 - attack として扱い、待っても見込みが減らないときは当たる確率が attack 以上で宣言する。risk と一緒には渡せない",
+                kind: ParamKind::Float {
+                    min: 0_f64,
+                    max: 1_f64,
+                },
+                default: None,
+            },
+        ],
+        needs_oracle: false,
+        build: |params, _| {
+            let risk = match (params.opt_f64("risk"), params.opt_f64("attack")) {
+                (Some(_), Some(_)) => {
+                    return Err("unfair takes either risk or attack, not both".to_string())
+                }
+                (None, Some(attack)) => 1_f64 - attack,
+                (risk, None) => risk.unwrap_or(0_f64),
+            };
+            Ok(Box::new(Unfair::new(DeclarePolicy::new(risk))))
+        },
+    },
+    Entry {
+        name: "ismcts",
+        help: "Information Set MCTS",
+        params: &[
+            Param {
+                name: "iterations",
+                help: "1 手ごとの反復回数",
+                kind: ParamKind::Int {
+                    min: 1,
+                    max: 1_000_000,
+                },
+                default: Some(200_f64),
+            },
+            Param {
+                name: "exploration",
+                help: "UCT の探索の強さ",
+                kind: ParamKind::Float {
+                    min: 0_f64,
+                    max: 10_f64,
+                },
+                default: Some(std::f64::consts::SQRT_2),
+            },
+            Param {
+                name: "rollout_depth",
+                help: "rollout でこの手数以内に決着がつかなければ引き分け",
+                kind: ParamKind::Int { min: 1, max: 1000 },
+                default: Some(30_f64),
+            },
+            Param {
+                name: "time_ms",
+                help: "1 手ごとの持ち時間。渡すと iterations に届かなくてもここで打ち切る（native のときだけ）",
+                kind: ParamKind::Int {
+                    min: 1,
+                    max: 600_000,
+                },
+                default: None,
+            },
+            SEED,
+        ],
+        needs_oracle: false,
+        build: |params, context| {
+            let config = IsmctsConfig {
+                iterations: params.usize("iterations"),
+                exploration: params.f64("exploration"),
+                rollout_depth: params.usize("rollout_depth"),
+                time_limit: time_limit(params)?,
+            };
+            let seed = params.seed(context).unwrap_or_else(time_seed);
+            Ok(Box::new(IsmctsPlayer::new(
+                SmallRng::seed_from_u64(seed),
+                config,
+            )))
+        },
+    },
+    Entry {
+        name: "bayes",
+        help: "相手の query から相手の手札を推定する",
+        params: &[Param {
+            name: "noise",
+            help: "相手が entropy 通りに打たない確率",
+            kind: ParamKind::Float {
+                min: 0_f64,
+                max: 1_f64,
+            },
+            default: Some(0.1),
+        }],
+        needs_oracle: false,
+        build: |params, _| {
+            Ok(Box::new(BayesPlayer::new(entropy_model(
+                params.f64("noise"),
+            ))))
+        },
+    },
+    Entry {
+        name: "cheater",
+        help: "本当の配置を知っている（上限を測るため）。既定ではすぐに本当の頭を宣言する",
+        params: &[Param {
+            name: "mode",
+            help:
+                "declare ならすぐ宣言し、solve なら一番短い手数で自分の頭を確定させてから宣言する",
+            kind: ParamKind::Text,
+            default: None,
+        }],
+        needs_oracle: true,
+        build: |params, context| {
+            let distr = context
+                .oracle
+                .clone()
+                .ok_or("cheater needs the true distr")?;
+            let mode = match params.text("mode").unwrap_or("declare") {
+                "declare" => CheaterMode::Declare,
+                "solve" => CheaterMode::Solve,
+                mode => return Err(format!("unknown cheater mode {mode} (declare|solve)")),
+            };
+            Ok(Box::new(CheaterPlayer::with_oracle(distr, mode)))
+        },
+    },
+    #[cfg(feature = "native")]
+    Entry {
+        name: "learned",
+        help: "game-train で学習した線形モデルで打つ",
+        params: &[Param {
+            name: "checkpoint",
+            help: "game-train が保存した checkpoint の JSON（必須）",
+            kind: ParamKind::Text,
+            default: None,
+        }],
+        needs_oracle: false,
+        build: |params, _| {
+            let path = params
+                .text("checkpoint")
+                .ok_or("learned needs checkpoint=PATH")?;
+            let player = LearnedPlayer::load(path)
+                .map_err(|err| format!("failed to load checkpoint {path}: {err}"))?;
+            Ok(Box::new(player))
+        },
+    },
+    #[cfg(feature = "native")]
+    Entry {
+        name: "cfr",
+        help: "game-ai-cfr が保存した平均戦略で打つ",
+        params: &[
+            Param {
+                name: "policy",
+                help: "game-ai-cfr が保存した policy の JSON（必須）",
+                kind: ParamKind::Text,
+                default: None,
+            },
+            SEED,
+        ],
+        needs_oracle: false,
+        build: |params, context| {
+            let path = params.text("policy").ok_or("cfr needs policy=PATH")?;
+            let policy = CfrPolicy::load(path)
+                .map_err(|err| format!("failed to load policy {path}: {err}"))?;
+            let seed = params.seed(context).unwrap_or_else(time_seed);
+            Ok(Box::new(CfrPlayer::from_policy(policy, seed)))
+        },
+    },
+    #[cfg(feature = "native")]
+    Entry {
+        name: "external",
+        help: "別のプロセスを立ち上げ、標準入出力の JSON で手を聞く（game-ai/external）",
+        params: &[
+            Param {
+                name: "cmd",
+                help: "立ち上げるコマンド（必須）。空白で区切って引数を渡す",
+                kind: ParamKind::Text,
+                default: None,
+            },
+            Param {
+                name: "handshake_ms",
+                help: "立ち上げてから返事を待つ時間",
+                kind: ParamKind::Int {
+                    min: 1,
+                    max: 600_000,
+                },
+                default: Some(10_000_f64),
+            },
+            Param {
+                name: "timeout_ms",
+                help: "1 手を待つ時間",
+                kind: ParamKind::Int {
+                    min: 1,
+                    max: 600_000,
+                },
+                default: Some(10_000_f64),
+            },
+        ],
+        needs_oracle: false,
+        build: |params, _| {
+            let cmd = params.text("cmd").ok_or("external needs cmd=COMMAND")?;
+            let command = cmd.split_whitespace().map(str::to_string).collect();
+            let options = ExternalOptions {
+                handshake_timeout: Duration::from_millis(params.usize("handshake_ms") as u64),
+                move_timeout: Duration::from_millis(params.usize("timeout_ms") as u64),
+            };
+            let player = ExternalPlayer::spawn(command, options)
+                .map_err(|err| format!("failed to start {cmd}: {err}"))?;
+            Ok(Box::new(player))
+        },
+    },
+];
+
+// meta の説明。meta は params ではなく MetaSpec を取るので ENTRIES には入れない
+pub const META_HELP: &str = "meta:vote:entropy=2,unfair / meta:phase=20:entropy,[search:depth=3] / meta:bandit=1:entropy,unfair,bayes : ほかの AI をまとめる（params つきの AI は [] で囲む）";
+
+pub fn entry(name: &str) -> Option<&'static Entry> {
+    ENTRIES.iter().find(|entry| entry.name == name)
+}
+
+// 使える spec の一覧
+pub fn help() -> String {
+    let mut lines = vec![];
+    for entry in ENTRIES {
+        lines.push(format!("{} : {}", entry.name, entry.help));
+        for param in entry.params {
+            let range = match param.kind {
+                ParamKind::Int { min, max } => format!("{min}..={max}"),
+                ParamKind::Float { min, max } => format!("{min}..={max}"),
+                ParamKind::Text => "TEXT".to_string(),
+            };
+            let default = match param.default {
+                Some(default) => format!(", default {default}"),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "    {}={range}{default} : {}",
+                param.name, param.help
+            ));
+        }
+    }
+    lines.push(META_HELP.to_string());
+    lines.join("\n")
+}
+
+// AI を作るときに front-end から渡すもの
+#[derive(Debug, Clone, Default)]
+pub struct BuildContext {
+    // seed を指定しない乱数を使う AI の seed。None なら時刻から決める
+    pub seed: Option<u64>,
+    // cheater に渡す本当の配置
+    pub oracle: Option<Distr>,
+}
+
+// 値を確かめた params。省略されたものは既定値が入っている
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params(BTreeMap<&'static str, String>);
+
+impl Params {
+    pub fn usize(&self, name: &str) -> usize {
+        self.0[name].parse().unwrap()
+    }
+
+    pub fn f64(&self, name: &str) -> f64 {
+        self.0[name].parse().unwrap()
+    }
+
+    // 既定値のない数の param。省略されていれば None
+    pub fn opt_f64(&self, name: &str) -> Option<f64> {
+        self.0.get(name).map(|value| value.parse().unwrap())
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|value| value.as_str())
+    }
+
+    fn seed(&self, context: &BuildContext) -> Option<u64> {
+        self.0
+            .get("seed")
+            .map(|seed| seed.parse().unwrap())
+            .or(context.seed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Args {
+    Params {
+        // 書かれた順の params。表示に使う
+        given: Vec<(String, String)>,
+        params: Params,
+    },
+    Meta {
+        meta: MetaSpec,
+        // meta.members を parse したもの
+        members: Vec<AgentSpec>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentSpec {
+    name: String,
+    args: Args,
+}
+
+impl AgentSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, rest) = match spec.split_once(':') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (spec, None),
+        };
+        if name == "meta" {
+            let meta = MetaSpec::parse(rest.unwrap_or_default())?;
+            let members = meta
+                .members
+                .iter()
+                .map(|(member, _)| {
+                    Self::parse(member).map_err(|err| format!("meta member {member}: {err}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for member in &members {
+                // cheater は試合ごとに作り直す必要があるので、試合をまたぐ bandit には入れられない
+                if member.needs_oracle() && matches!(meta.mode, MetaMode::Bandit { .. }) {
+                    return Err(format!("{member} can not be used in bandit"));
+                }
+            }
+            return Ok(Self {
+                name: name.to_string(),
+                args: Args::Meta { meta, members },
+            });
+        }
+
+        let entry = entry(name).ok_or_else(|| format!("unknown strategy: {name}"))?;
+        let mut given = vec![];
+        for param in rest.into_iter().flat_map(|rest| rest.split(',')) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value: {param}"))?;
+            given.push((key.to_string(), value.to_string()));
+        }
+        let mut params = BTreeMap::new();
+        for param in entry.params {
+            if let Some(default) = param.default {
+                params.insert(param.name, default.to_string());
+            }
+        }
+        for (key, value) in &given {
+            let param = entry
+                .params
+                .iter()
+                .find(|param| param.name == key)
+                .ok_or_else(|| format!("unknown parameter for {name}: {key}"))?;
+            check_value(param, value).map_err(|err| format!("{name}:{key}: {err}"))?;
+            params.insert(param.name, value.clone());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            args: Args::Params {
+                given,
+                params: Params(params),
+            },
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // meta なら None
+    pub fn params(&self) -> Option<&Params> {
+        match &self.args {
+            Args::Params { params, .. } => Some(params),
+            Args::Meta { .. } => None,
+        }
+    }
+
+    // 序盤の本を引ける AI なら、本の (名前, 版)。build-book はこの名前で保存する
+    pub fn book(&self) -> Option<(String, u32)> {
+        let params = self.params()?;
+        match self.name.as_str() {
+            "entropy" => Some((
+                UseEntropyPlayer::BOOK_AGENT.to_string(),
+                UseEntropyPlayer::BOOK_VERSION,
+            )),
+            "search" => Some((
+                SearchPlayer::book_agent(params.usize("depth"), params.usize("samples")),
+                SearchPlayer::BOOK_VERSION,
+            )),
+            _ => None,
+        }
+    }
+
+    // true なら BuildContext::oracle を渡すこと
+    pub fn needs_oracle(&self) -> bool {
+        match &self.args {
+            Args::Params { .. } => entry(&self.name).unwrap().needs_oracle,
+            Args::Meta { members, .. } => members.iter().any(AgentSpec::needs_oracle),
+        }
+    }
+
+    // 試合をまたいで同じ AI を使い続けるべきか。bandit は前の試合の勝敗から学ぶ
+    pub fn learns_across_games(&self) -> bool {
+        matches!(
+            &self.args,
+            Args::Meta {
+                meta: MetaSpec {
+                    mode: MetaMode::Bandit { .. },
+                    ..
+                },
+                ..
+            }
+        )
+    }
+
+    pub fn build(&self, context: &BuildContext) -> Result<BoxedAgent, String> {
+        if self.needs_oracle() && context.oracle.is_none() {
+            return Err(format!("{self} needs the true distr"));
+        }
+        match &self.args {
+            Args::Params { params, .. } => (entry(&self.name).unwrap().build)(params, context),
+            Args::Meta { meta, members } => {
+                // 作れない AI があればエラーにするため、先に全部作っておく
+                let mut members = members
+                    .iter()
+                    .map(|member| member.build(context))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter();
+                Ok(Box::new(MetaPlayer::new(meta, |_| members.next().unwrap())))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+fn time_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+// wasm32-unknown-unknown では SystemTime::now が panic するので、seed は BuildContext で渡すこと
+#[cfg(not(feature = "native"))]
+fn time_seed() -> u64 {
+    0
+}
+
+// wasm では Instant が使えないので、時間で打ち切るのは native のときだけ
+#[cfg(feature = "native")]
+fn time_limit(params: &Params) -> Result<Option<Duration>, String> {
+    Ok(params
+        .opt_f64("time_ms")
+        .map(|ms| Duration::from_millis(ms as u64)))
+}
+
+#[cfg(not(feature = "native"))]
+fn time_limit(params: &Params) -> Result<Option<std::time::Duration>, String> {
+    match params.opt_f64("time_ms") {
+        Some(_) => Err("time_ms needs the native feature".to_string()),
+        None => Ok(None),
+    }
+}
+
+// risk が渡されていれば、宣言するかどうかを DeclarePolicy で決めるように包む
+fn with_risk<A>(agent: A, params: &Params) -> BoxedAgent
+where
+    A: Agent<Game = Game> + 'static,
+{
+    match params.opt_f64("risk") {
+        Some(risk) => Box::new(WithDeclarePolicy::new(agent, DeclarePolicy::new(risk))),
+        None => Box::new(agent),
+    }
+}
+
+fn check_value(param: &Param, value: &str) -> Result<(), String> {
+    match param.kind {
+        ParamKind::Int { min, max } => {
+            let value: u64 = value
+                .parse()
+                .map_err(|_| format!("expected an integer: {value}"))?;
+            if !(min..=max).contains(&value) {
+                return Err(format!("{value} is out of {min}..={max}"));
+            }
+        }
+        ParamKind::Float { min, max } => {
+            let value: f64 = value
+                .parse()
+                .map_err(|_| format!("expected a number: {value}"))?;
+            if !(min..=max).contains(&value) {
+                return Err(format!("{value} is out of {min}..={max}"));
+            }
+        }
+        ParamKind::Text => {}
+    }
+    Ok(())
+}
+
+impl fmt::Display for AgentSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.args {
+            Args::Params { given, .. } if given.is_empty() => write!(f, "{}", self.name),
+            Args::Params { given, .. } => {
+                let given: Vec<String> = given
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect();
+                write!(f, "{}:{}", self.name, given.join(","))
+            }
+            Args::Meta { meta, .. } => write!(f, "meta:{meta}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::{abstract_game::ImperfectInfoGame, config::three_small, defs::Move};
+
+    use super::*;
+
+    #[test]
+    fn parse_validates_params() {
+        let spec = AgentSpec::parse("search:depth=3").unwrap();
+        assert_eq!(spec.to_string(), "search:depth=3");
+        assert!(spec.build(&BuildContext::default()).is_ok());
+        assert_eq!(
+            AgentSpec::parse("unfair:risk=0.5").unwrap().name(),
+            "unfair"
+        );
+        // attack は risk = 1 - attack の別名
+        let unfair = AgentSpec::parse("unfair:attack=0.55").unwrap();
+        assert!(unfair.build(&BuildContext::default()).is_ok());
+        let both = AgentSpec::parse("unfair:attack=0.55,risk=0.1").unwrap();
+        assert!(both.build(&BuildContext::default()).is_err());
+        // risk をつけると DeclarePolicy で包まれ、risk=1 なら最初の手番から宣言する
+        let (info, moves) = three_small().gen_random(0).info_and_move_now();
+        assert!(AgentSpec::parse("ismcts:iterations=10,time_ms=50").is_ok());
+        for spec in ["entropy:risk=1", "search:samples=0,risk=1"] {
+            let mut agent = AgentSpec::parse(spec)
+                .unwrap()
+                .build(&BuildContext::default())
+                .unwrap();
+            let m = agent.use_info(info.clone(), moves.clone());
+            assert!(matches!(m, Move::Declare { .. }), "{spec}");
+        }
+
+        assert!(AgentSpec::parse("search:depth=0").is_err());
+        assert!(AgentSpec::parse("search:width=3").is_err());
+        assert!(AgentSpec::parse("random:seed=x").is_err());
+        assert!(AgentSpec::parse("unknown").is_err());
+        assert!(AgentSpec::parse("meta:bandit:entropy,cheater").is_err());
+        #[cfg(feature = "native")]
+        {
+            let learned = AgentSpec::parse("learned:checkpoint=/nonexistent.json").unwrap();
+            assert!(learned.build(&BuildContext::default()).is_err());
+            let cfr = AgentSpec::parse("cfr:policy=/nonexistent.json,seed=1").unwrap();
+            assert!(cfr.build(&BuildContext::default()).is_err());
+            let external = AgentSpec::parse("external:cmd=/nonexistent --flag").unwrap();
+            assert!(external.build(&BuildContext::default()).is_err());
+        }
+
+        let meta = AgentSpec::parse("meta:vote:random,cheater").unwrap();
+        assert!(meta.needs_oracle());
+        assert!(meta.build(&BuildContext::default()).is_err());
+        let context = BuildContext {
+            seed: Some(7),
+            oracle: Some(three_small().gen_random(0).distr()),
+        };
+        assert!(meta.build(&context).is_ok());
+        // 中の AI にも params を渡せる
+        let meta = AgentSpec::parse("meta:phase=20:entropy,[search:depth=3,samples=0]").unwrap();
+        assert_eq!(
+            meta.to_string(),
+            "meta:phase=20:entropy,[search:depth=3,samples=0]"
+        );
+        assert!(meta.build(&context).is_ok());
+        assert!(AgentSpec::parse("meta:vote:entropy,[search:depth=0]").is_err());
+        assert!(AgentSpec::parse("meta:bandit:entropy,[cheater:mode=solve]").is_err());
+        let solve = AgentSpec::parse("cheater:mode=solve").unwrap();
+        assert!(solve.build(&context).is_ok());
+        let unknown = AgentSpec::parse("cheater:mode=peek").unwrap();
+        assert!(unknown.build(&context).is_err());
+        assert!(AgentSpec::parse("meta:bandit:entropy,unfair")
+            .unwrap()
+            .learns_across_games());
+        assert_eq!(
+            AgentSpec::parse("search:samples=0,book=1").unwrap().book(),
+            Some(("search-d2-s0".to_string(), SearchPlayer::BOOK_VERSION))
+        );
+        assert_eq!(AgentSpec::parse("unfair").unwrap().book(), None);
+
+        // 全部の既定値が範囲に入っている
+        for entry in ENTRIES {
+            assert!(AgentSpec::parse(entry.name).is_ok());
+            for param in entry.params {
+                if let Some(default) = param.default {
+                    assert!(check_value(param, &default.to_string()).is_ok());
+                }
+            }
+        }
+    }
+}
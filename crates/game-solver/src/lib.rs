@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame, Player},
+    defs::*,
+    utils::*,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+// 小さい config 向けの厳密な計算。
+// - 配り方は全部列挙して、一様に起こるものとする。
+// - Agent は Info から手を返す決定的な関数とみなす（シード付きの random も一つの純粋戦略として扱う）。
+// - Agent が内部状態を持っていてもよいように、分岐するときはそのゲームの履歴を最初から再生して作り直す。
+// - max_turns 手で決着がつかなければ誰も勝たない（unresolved）とする。
+// - Agent には Runner::run_game と同じように on_game_start / observe / on_game_end を呼ぶ。
+
+pub type AgentFactory<'a> = &'a dyn Fn(Player) -> Box<dyn Agent<Game = Game>>;
+
+// seat から見た情報集合: (view, 履歴)
+type InfoSetKey = (View, Vec<MoveAns>);
+
+// 全ての配り方。各配り方はちょうど一度ずつ出てくる。
+pub fn all_deals(config: &GameConfig) -> Vec<Distr> {
+    let mut deals: Vec<(Distr, Vec<Card>)> = vec![(vec![], config.all_cards())];
+    for _ in 0..config.player_num() {
+        deals = deals
+            .into_iter()
+            .flat_map(|(distr, rest)| {
+                rest.clone()
+                    .into_iter()
+                    .combinations(config.hand_num())
+                    .flat_map(move |hand| {
+                        let rest: Vec<Card> =
+                            rest.iter().filter(|c| !hand.contains(c)).cloned().collect();
+                        let distr = distr.clone();
+                        rest.clone()
+                            .into_iter()
+                            .combinations(config.head_num())
+                            .map(move |head| {
+                                let rest =
+                                    rest.iter().filter(|c| !head.contains(c)).cloned().collect();
+                                let mut distr = distr.clone();
+                                distr.push(PlCard {
+                                    hand: hand.iter().cloned().collect(),
+                                    head: head.into_iter().collect(),
+                                });
+                                (distr, rest)
+                            })
+                    })
+            })
+            .collect();
+    }
+    deals.into_iter().map(|(distr, _)| distr).collect()
+}
+
+// gen_random で n 個配る。大きい config では all_deals の代わりにこれを使う。
+pub fn sample_deals(config: &GameConfig, n: usize, seed: usize) -> Vec<Distr> {
+    (0..n)
+        .map(|i| config.gen_random(seed + i).distr())
+        .collect()
+}
+
+// seat から最初に見えるもの（自分の手札と他人の頭）が distr と同じ配り方の全体。
+pub fn deals_with_same_view(config: &GameConfig, seat: Player, distr: &Distr) -> Vec<Distr> {
+    let unseen: Vec<Card> = config
+        .all_cards()
+        .into_iter()
+        .filter(|c| {
+            distr.iter().enumerate().all(|(p, pl)| {
+                !(p == seat && pl.hand.contains(c) || p != seat && pl.head.contains(c))
+            })
+        })
+        .collect();
+    let mut deals: Vec<(Distr, Vec<Card>)> = vec![(vec![], unseen)];
+    for (p, pl) in distr.iter().enumerate() {
+        let k = if p == seat {
+            config.head_num()
+        } else {
+            config.hand_num()
+        };
+        deals = deals
+            .into_iter()
+            .flat_map(|(partial, rest)| {
+                rest.clone().into_iter().combinations(k).map(move |chosen| {
+                    let rest = rest
+                        .iter()
+                        .filter(|c| !chosen.contains(c))
+                        .cloned()
+                        .collect();
+                    let chosen = chosen.into_iter().collect();
+                    let mut partial = partial.clone();
+                    partial.push(if p == seat {
+                        PlCard {
+                            hand: pl.hand.clone(),
+                            head: chosen,
+                        }
+                    } else {
+                        PlCard {
+                            hand: chosen,
+                            head: pl.head.clone(),
+                        }
+                    });
+                    (partial, rest)
+                })
+            })
+            .collect();
+    }
+    deals.into_iter().map(|(distr, _)| distr).collect()
+}
+
+// deals に出てくる seat の最初の見え方それぞれについて、同じ見え方の配り方を全部集める。
+// 見え方ごとに配り方の数は同じなので、サンプルした見え方の上で一様になる。
+// all_deals を渡せば all_deals と同じ集合になる。
+pub fn close_over_views(config: &GameConfig, seat: Player, deals: &[Distr]) -> Vec<Distr> {
+    let mut views: Vec<View> = vec![];
+    let mut closed = vec![];
+    for distr in deals {
+        let view = cards_from_player(distr, seat);
+        if views.contains(&view) {
+            continue;
+        }
+        views.push(view);
+        closed.extend(deals_with_same_view(config, seat, distr));
+    }
+    closed
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileValue {
+    pub deals: usize,
+    // win[i] = player i が勝つ確率
+    pub win: Vec<f64>,
+    pub unresolved: f64,
+}
+
+// 全員が factory の Agent で打ったときの、各 seat の勝率。
+pub fn profile_value(
+    config: &GameConfig,
+    deals: &[Distr],
+    factory: AgentFactory,
+    max_turns: usize,
+) -> ProfileValue {
+    let player_num = config.player_num();
+    let mut win = vec![0_f64; player_num];
+    let mut unresolved = 0_f64;
+    for distr in deals {
+        let mut game = config.game_from_distr(distr.clone());
+        let mut agents: Vec<_> = (0..player_num).map(factory).collect();
+        for (seat, agent) in agents.iter_mut().enumerate() {
+            agent.on_game_start(config, seat);
+        }
+        while game.is_win().is_none() && game.history().len() < max_turns {
+            let player = game.player_turn();
+            let (info, possible_moves) = game.info_and_move_now();
+            let action = agents[player].use_info(info, possible_moves);
+            if !game.move_game(action) {
+                panic!("動けるやつにして");
+            }
+            let last = game.history().pop().unwrap();
+            for agent in agents.iter_mut() {
+                agent.observe(&last);
+            }
+        }
+        let winner = game.is_win();
+        let outcome = winner.clone().unwrap_or_else(|| vec![0; player_num]);
+        for agent in agents.iter_mut() {
+            agent.on_game_end(&outcome);
+        }
+        match winner {
+            Some(winner) => {
+                for (w, v) in win.iter_mut().zip(winner) {
+                    *w += v as f64;
+                }
+            }
+            None => unresolved += 1_f64,
+        }
+    }
+    let n = deals.len() as f64;
+    ProfileValue {
+        deals: deals.len(),
+        win: win.into_iter().map(|w| w / n).collect(),
+        unresolved: unresolved / n,
+    }
+}
+
+// seat 以外は factory の Agent で打つとき、seat が最善に打った場合の勝率。
+pub fn best_response_value(
+    config: &GameConfig,
+    deals: &[Distr],
+    seat: Player,
+    factory: AgentFactory,
+    max_turns: usize,
+) -> f64 {
+    best_response(config, deals, seat, factory, max_turns).value
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BestResponseEntry {
+    pub view: View,
+    pub history: Vec<MoveAns>,
+    pub action: Move,
+}
+
+// 最善応答の値と、そのときに seat がとる手（調べた情報集合ごと）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BestResponse {
+    pub seat: Player,
+    pub value: f64,
+    pub policy: Vec<BestResponseEntry>,
+}
+
+// seat 以外は factory の Agent で打つときの、seat の最善応答。
+// seat の手は reasonable_moves に限る（確定した宣言は常に最善なので、それ以外を除いても値は変わらない）。
+// deals が全ての配り方なら厳密な値になる。
+// サンプルした配り方を使うときは close_over_views で seat の情報集合を閉じておくこと。
+pub fn best_response(
+    config: &GameConfig,
+    deals: &[Distr],
+    seat: Player,
+    factory: AgentFactory,
+    max_turns: usize,
+) -> BestResponse {
+    let games = deals
+        .iter()
+        .map(|distr| config.game_from_distr(distr.clone()))
+        .collect();
+    let mut solver = BestResponseSolver {
+        config,
+        seat,
+        factory,
+        max_turns,
+        policy: vec![],
+    };
+    let value = solver.advance_and_group(games) / deals.len() as f64;
+    BestResponse {
+        seat,
+        value,
+        policy: solver.policy,
+    }
+}
+
+struct BestResponseSolver<'a> {
+    config: &'a GameConfig,
+    seat: Player,
+    factory: AgentFactory<'a>,
+    max_turns: usize,
+    policy: Vec<BestResponseEntry>,
+}
+
+impl BestResponseSolver<'_> {
+    // 各ゲームを seat の手番まで進め、 seat の情報集合ごとに分けて、勝つゲーム数の和を返す。
+    fn advance_and_group(&mut self, games: Vec<Game>) -> f64 {
+        let mut value = 0_f64;
+        let mut groups: Vec<(InfoSetKey, Vec<Game>)> = vec![];
+        for mut game in games {
+            self.advance_others(&mut game);
+            if let Some(winner) = game.is_win() {
+                value += winner[self.seat] as f64;
+                continue;
+            }
+            if game.history().len() >= self.max_turns {
+                continue;
+            }
+            let key = (game.view_from_player(self.seat), game.history());
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, group)) => group.push(game),
+                None => groups.push((key, vec![game])),
+            }
+        }
+        value
+            + groups
+                .into_iter()
+                .map(|((view, history), group)| self.best_in_info_set(view, history, group))
+                .sum::<f64>()
+    }
+
+    // games は全て seat の手番で、 seat から見て区別できない。
+    fn best_in_info_set(&mut self, view: View, history: Vec<MoveAns>, games: Vec<Game>) -> f64 {
+        let mut best: Option<(Move, f64)> = None;
+        for m in reasonable_moves(self.config, &history, &view) {
+            let next = games
+                .iter()
+                .map(|game| {
+                    let mut game = game.clone();
+                    if !game.move_game(m.clone()) {
+                        unreachable!("reasonable_moves は動ける手");
+                    }
+                    game
+                })
+                .collect();
+            let value = self.advance_and_group(next);
+            if best.as_ref().is_none_or(|(_, v)| *v < value) {
+                best = Some((m, value));
+            }
+        }
+        let Some((action, value)) = best else {
+            return 0_f64;
+        };
+        self.policy.push(BestResponseEntry {
+            view,
+            history,
+            action,
+        });
+        value
+    }
+
+    fn advance_others(&self, game: &mut Game) {
+        while game.is_win().is_none()
+            && game.history().len() < self.max_turns
+            && game.player_turn() != self.seat
+        {
+            let action = replayed_move(self.config, game, self.factory);
+            if !game.move_game(action) {
+                panic!("動けるやつにして");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeatExploitability {
+    pub seat: Player,
+    // 今の Agent での勝率
+    pub value: f64,
+    // 最善応答の値と手。--policy-out で保存するのでそのまま持っておく
+    pub best_response: BestResponse,
+    // 最善応答に置き換えたときに増える勝率
+    pub exploitability: f64,
+}
+
+// 各 seat について、その seat だけを最善応答に置き換えたときにどれだけ勝率が上がるか。
+// サンプルした配り方を渡した場合も、 seat から見て区別できない配り方は全部含めて計算する
+// （そうしないと最善応答が配り方を見分けられてしまい、値が大きく出すぎる）。
+pub fn exploitability(
+    config: &GameConfig,
+    deals: &[Distr],
+    factory: AgentFactory,
+    max_turns: usize,
+    seats: &[Player],
+) -> Vec<SeatExploitability> {
+    seats
+        .iter()
+        .map(|&seat| {
+            let deals = close_over_views(config, seat, deals);
+            let value = profile_value(config, &deals, factory, max_turns).win[seat];
+            let best_response = best_response(config, &deals, seat, factory, max_turns);
+            SeatExploitability {
+                seat,
+                value,
+                exploitability: best_response.value - value,
+                best_response,
+            }
+        })
+        .collect()
+}
+
+// 最善応答の手に従って打つ。載っていない情報集合では reasonable_moves の最初の手を打つ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BestResponsePlayer {
+    table: HashMap<String, Move>,
+}
+
+impl BestResponsePlayer {
+    pub fn new(best_response: &BestResponse) -> Self {
+        let table = best_response
+            .policy
+            .iter()
+            .map(|entry| (entry_key(&entry.view, &entry.history), entry.action.clone()))
+            .collect();
+        Self { table }
+    }
+}
+
+fn entry_key(view: &View, history: &[MoveAns]) -> String {
+    serde_json::to_string(&(view, history)).expect("failed to serialize info set")
+}
+
+impl Agent for BestResponsePlayer {
+    type Game = Game;
+
+    fn use_info(
+        &mut self,
+        info: <Self::Game as ImperfectInfoGame>::Info,
+        _possible_moves: Vec<<Self::Game as ImperfectInfoGame>::Move>,
+    ) -> <Self::Game as ImperfectInfoGame>::Move {
+        if let Some(m) = self.table.get(&entry_key(&info.view, &info.query_answer)) {
+            return m.clone();
+        }
+        reasonable_moves(&info.config, &info.query_answer, &info.view)
+            .into_iter()
+            .next()
+            .unwrap()
+    }
+}
+
+// 手番のプレイヤーの Agent を作り直し、そのプレイヤーの過去の手番を再生してから今の手を聞く。
+pub fn replayed_move(config: &GameConfig, game: &Game, factory: AgentFactory) -> Move {
+    let player = game.player_turn();
+    let mut agent = factory(player);
+    agent.on_game_start(config, player);
+    let mut replay = config.game_from_distr(game.distr());
+    for qa in game.history() {
+        if replay.player_turn() == player {
+            let (info, possible_moves) = replay.info_and_move_now();
+            let _ = agent.use_info(info, possible_moves);
+        }
+        replay.move_game(qa.move_of_this());
+        agent.observe(&qa);
+    }
+    let (info, possible_moves) = game.info_and_move_now();
+    agent.use_info(info, possible_moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use game_core::config::three_small;
+    use game_registry::AgentSpec;
+    use game_stats::build_agent;
+
+    use super::*;
+
+    #[test]
+    fn exact_values_on_three_small() {
+        let config = three_small();
+        let deals = all_deals(&config);
+        assert_eq!(deals.len(), 720);
+        for (i, distr) in deals.iter().enumerate() {
+            assert!(!deals[i + 1..].contains(distr));
+        }
+        assert_eq!(close_over_views(&config, 1, &deals).len(), 720);
+        assert_eq!(close_over_views(&config, 1, &deals[..1]).len(), 6);
+
+        let factory = |player| build_agent(&AgentSpec::parse("entropy").unwrap(), 0, player);
+        let value = profile_value(&config, &deals, &factory, 12);
+        let total: f64 = value.win.iter().sum::<f64>() + value.unresolved;
+        assert!((total - 1_f64).abs() < 1e-9);
+
+        // 最善応答は重いので、一部の配り方と短い手数で確かめる
+        let deals = &deals[..60];
+        let value = profile_value(&config, deals, &factory, 4);
+        let br = best_response_value(&config, deals, 0, &factory, 4);
+        assert!(br + 1e-9 >= value.win[0]);
+
+        // 最善応答の手で実際に打つと、同じ勝率になる
+        let response = best_response(&config, deals, 0, &factory, 4);
+        let policy_factory = |player| -> Box<dyn Agent<Game = Game>> {
+            if player == 0 {
+                Box::new(BestResponsePlayer::new(&response))
+            } else {
+                build_agent(&AgentSpec::parse("entropy").unwrap(), 0, player)
+            }
+        };
+        let played = profile_value(&config, deals, &policy_factory, 4);
+        assert!((played.win[0] - response.value).abs() < 1e-9);
+    }
+
+    // 呼ばれた hook を数える
+    struct Counting {
+        inner: Box<dyn Agent<Game = Game>>,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Agent for Counting {
+        type Game = Game;
+        fn use_info(&mut self, info: Info, possible_moves: Vec<Move>) -> Move {
+            self.inner.use_info(info, possible_moves)
+        }
+        fn on_game_start(&mut self, config: &GameConfig, seat: Player) {
+            self.calls.lock().unwrap().push("start");
+            self.inner.on_game_start(config, seat);
+        }
+        fn observe(&mut self, move_ans: &MoveAns) {
+            self.calls.lock().unwrap().push("observe");
+            self.inner.observe(move_ans);
+        }
+        fn on_game_end(&mut self, outcome: &[usize]) {
+            self.calls.lock().unwrap().push("end");
+            self.inner.on_game_end(outcome);
+        }
+    }
+
+    #[test]
+    fn agents_see_the_game_lifecycle() {
+        let config = three_small();
+        let deals = all_deals(&config);
+        let calls = Arc::new(Mutex::new(vec![]));
+        let factory = |player| -> Box<dyn Agent<Game = Game>> {
+            Box::new(Counting {
+                inner: build_agent(&AgentSpec::parse("entropy").unwrap(), 0, player),
+                calls: calls.clone(),
+            })
+        };
+
+        // 全員が始まりと終わりを一度ずつ受け取り、その間に全員が全ての手を見る
+        profile_value(&config, &deals[..1], &factory, 1000);
+        let played = calls.lock().unwrap().clone();
+        let observed = count_of(&calls, "observe");
+        assert!(observed > 0 && observed.is_multiple_of(3));
+        assert_eq!(played[..3], ["start"; 3]);
+        assert_eq!(played[3..3 + observed], vec!["observe"; observed]);
+        assert_eq!(played[3 + observed..], ["end"; 3]);
+
+        // 再生するときも、作り直した Agent に始まりとそれまでの手を渡す
+        calls.lock().unwrap().clear();
+        let mut game = config.game_from_distr(deals[0].clone());
+        for turn in 0..3 {
+            let action = replayed_move(&config, &game, &factory);
+            game.move_game(action);
+            assert_eq!(count_of(&calls, "start"), turn + 1);
+        }
+        assert_eq!(count_of(&calls, "observe"), 1 + 2);
+    }
+
+    fn count_of(calls: &Mutex<Vec<&'static str>>, name: &str) -> usize {
+        calls.lock().unwrap().iter().filter(|&&c| c == name).count()
+    }
+}
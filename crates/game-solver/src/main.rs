@@ -0,0 +1,180 @@
+use std::{fs::File, io::BufWriter};
+
+use game_core::{abstract_game::Player, config};
+use game_registry::AgentSpec;
+use game_solver::{all_deals, exploitability, profile_value, sample_deals};
+use game_stats::build_agent;
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(message) => usage_and_exit(&message),
+    };
+
+    let config = config::by_name(&args.config)
+        .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {}", args.config)));
+    if args.strategies.len() != config.player_num() {
+        usage_and_exit(&format!(
+            "expected {} strategies for {}",
+            config.player_num(),
+            args.config
+        ));
+    }
+    let seats: Vec<Player> = match args.seat {
+        Some(seat) if seat < config.player_num() => vec![seat],
+        Some(seat) => usage_and_exit(&format!("seat {seat} is out of range")),
+        None => (0..config.player_num()).collect(),
+    };
+
+    // --sample がなければ全ての配り方で厳密に計算する。
+    // --sample のときは、配った数だけ各 seat の見え方をサンプルし、その中は厳密に計算する。
+    let (deals, mode) = match args.sample {
+        Some(n) => (sample_deals(&config, n, args.seed), "sampled"),
+        None => (all_deals(&config), "exact"),
+    };
+    let factory = |player: Player| build_agent(&args.strategies[player], args.seed, player);
+    // 最善応答は全ての履歴を列挙するので手数を短く切る。勝率だけならゲームが終わるまで打つ
+    let max_turns = args.max_turns.unwrap_or(if args.best_response {
+        BEST_RESPONSE_MAX_TURNS
+    } else {
+        PROFILE_MAX_TURNS
+    });
+
+    println!(
+        "# config {} deals {} ({mode}) max_turns {}",
+        args.config,
+        deals.len(),
+        max_turns
+    );
+    if !args.best_response {
+        let value = profile_value(&config, &deals, &factory, max_turns);
+        println!("# seat strategy win_rate");
+        for (seat, strategy) in args.strategies.iter().enumerate() {
+            println!("{seat} {} {:.6}", strategy, value.win[seat]);
+        }
+        println!("# unresolved {:.6}", value.unresolved);
+        // 決着のつかないゲームがあれば勝率は厳密ではないので、黙って成功にはしない
+        if value.unresolved > 0_f64 {
+            eprintln!(
+                "{:.6} of the games did not finish within {max_turns} turns; the win rates are truncated",
+                value.unresolved
+            );
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    println!("# seat strategy win_rate best_response exploitability");
+    let reports = exploitability(&config, &deals, &factory, max_turns, &seats);
+    for report in &reports {
+        println!(
+            "{} {} {:.6} {:.6} {:.6}",
+            report.seat,
+            args.strategies[report.seat],
+            report.value,
+            report.best_response.value,
+            report.exploitability
+        );
+    }
+
+    if let Some(path) = args.policy_out {
+        let responses: Vec<_> = reports.iter().map(|report| &report.best_response).collect();
+        let writer = BufWriter::new(File::create(&path).expect("failed to create policy file"));
+        serde_json::to_writer(writer, &responses).expect("failed to write policy file");
+        eprintln!("saved best response policy to {path}");
+    }
+}
+
+const BEST_RESPONSE_MAX_TURNS: usize = 6;
+const PROFILE_MAX_TURNS: usize = 1000;
+
+struct Args {
+    config: String,
+    strategies: Vec<AgentSpec>,
+    max_turns: Option<usize>,
+    best_response: bool,
+    sample: Option<usize>,
+    seed: usize,
+    seat: Option<Player>,
+    policy_out: Option<String>,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let Some(config) = args.first() else {
+        return Err("missing config name".to_string());
+    };
+
+    let mut parsed = Args {
+        config: config.clone(),
+        strategies: vec![],
+        max_turns: None,
+        best_response: true,
+        sample: None,
+        seed: 0,
+        seat: None,
+        policy_out: None,
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--max-turns" => {
+                i += 1;
+                parsed.max_turns = Some(parse_usize(args.get(i), "--max-turns")?);
+            }
+            "--sample" => {
+                i += 1;
+                parsed.sample = Some(parse_usize(args.get(i), "--sample")?);
+            }
+            "--seed" => {
+                i += 1;
+                parsed.seed = parse_usize(args.get(i), "--seed")?;
+            }
+            "--seat" => {
+                i += 1;
+                parsed.seat = Some(parse_usize(args.get(i), "--seat")?);
+            }
+            "--policy-out" => {
+                i += 1;
+                parsed.policy_out = Some(
+                    args.get(i)
+                        .cloned()
+                        .ok_or("missing value for --policy-out")?,
+                );
+            }
+            "--no-best-response" => parsed.best_response = false,
+            name => {
+                let strategy = AgentSpec::parse(name)?;
+                // cheater は配り方ごとに作る必要があり、ここの factory では作れない
+                if strategy.needs_oracle() {
+                    return Err("cheater is not supported by game-solver".into());
+                }
+                parsed.strategies.push(strategy);
+            }
+        }
+        i += 1;
+    }
+
+    Ok(parsed)
+}
+
+fn parse_usize(value: Option<&String>, flag: &str) -> Result<usize, String> {
+    value
+        .ok_or_else(|| format!("missing value for {flag}"))?
+        .parse::<usize>()
+        .map_err(|_| format!("invalid integer for {flag}"))
+}
+
+fn usage_and_exit(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: game-solver <config> <p0> <p1> ... [--max-turns N] [--sample N] [--seed N] [--seat S] [--policy-out PATH] [--no-best-response]");
+    eprintln!(
+        "best response enumerates every history up to --max-turns (default {BEST_RESPONSE_MAX_TURNS}), so keep it small"
+    );
+    eprintln!(
+        "--no-best-response plays up to --max-turns (default {PROFILE_MAX_TURNS}) and exits with 1 if some games are unresolved"
+    );
+    eprintln!("without --sample every deal is enumerated (exact); use --sample for larger configs");
+    eprintln!("example: cargo run --release -p game-solver -- three_small entropy unfair random");
+    std::process::exit(2);
+}
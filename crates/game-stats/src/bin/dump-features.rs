@@ -0,0 +1,91 @@
+use std::io::{self, BufRead, BufWriter, Write};
+
+use game_core::{
+    abstract_game::ImperfectInfoGame,
+    config,
+    defs::{Move, MoveAns},
+    encoding::{Encoder, ENCODING_VERSION},
+};
+use serde::{Deserialize, Serialize};
+
+// game-stats の records.jsonl を標準入力から読み、各手を game_core::encoding で数値にして 1 行ずつ出す。
+// 配り方は記録の seed から作り直すので、game-stats と同じ gen_random を使っている必要がある。
+// --layout なら並びの説明（EncodingHeader）だけを出す。
+
+#[derive(Deserialize)]
+struct Record {
+    config: String,
+    seed: usize,
+    winner: Vec<usize>,
+    history: Vec<MoveAns>,
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+    version: u32,
+    seed: usize,
+    turn: usize,
+    player: usize,
+    action: &'a Move,
+    // その手を打ったプレイヤーが最後に勝ったか
+    won: usize,
+    features: Vec<f32>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    if args.first().map(String::as_str) == Some("--layout") {
+        let name = args
+            .get(1)
+            .cloned()
+            .unwrap_or_else(|| game_stats::stats_config_name().to_string());
+        let config = config::by_name(&name)
+            .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {name}")));
+        serde_json::to_writer(&mut writer, &Encoder::long_history(config).header())
+            .expect("failed to serialize header");
+        writer.write_all(b"\n").expect("failed to write newline");
+        return;
+    }
+    if !args.is_empty() {
+        usage_and_exit(&format!("unknown argument: {}", args[0]));
+    }
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("failed to read records");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line).expect("invalid record");
+        let config = config::by_name(&record.config)
+            .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {}", record.config)));
+        let encoder = Encoder::long_history(config.clone());
+        let mut game = config.gen_random(record.seed);
+        for (turn, move_ans) in record.history.iter().enumerate() {
+            let player = game.player_turn();
+            let (info, _) = game.info_and_move_now();
+            let action = move_ans.move_of_this();
+            let row = Row {
+                version: ENCODING_VERSION,
+                seed: record.seed,
+                turn,
+                player,
+                action: &action,
+                won: record.winner[player],
+                features: encoder.encode(&info, &action),
+            };
+            serde_json::to_writer(&mut writer, &row).expect("failed to serialize row");
+            writer.write_all(b"\n").expect("failed to write newline");
+            assert!(game.move_game(action), "record does not match its seed");
+        }
+    }
+    writer.flush().expect("failed to flush rows");
+}
+
+fn usage_and_exit(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: dump-features [--layout [CONFIG]] < records.jsonl > features.jsonl");
+    std::process::exit(2);
+}
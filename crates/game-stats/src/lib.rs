@@ -1,48 +1,18 @@
 use std::time::Instant;
 
-use game_ai_entropy::UseEntropyPlayer;
-use game_ai_random::RandomPlayer;
-use game_ai_search::SearchPlayer;
-use game_ai_unfair::Unfair;
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, ImperfectInfoGame, TurnRationale},
     config::three_midium,
-    defs::{Game, GameConfig, Move, MoveAns},
+    defs::{Distr, Game, GameConfig, Move, MoveAns},
+    runner::{FaultRecord, Runner, RunnerConfig},
 };
-use rand::{rngs::SmallRng, SeedableRng};
+use game_registry::{AgentSpec, BuildContext};
 use serde::Serialize;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Strategy {
-    Random,
-    Entropy,
-    Search,
-    Unfair,
-}
-
-impl Strategy {
-    pub fn parse(name: &str) -> Option<Self> {
-        match name {
-            "random" => Some(Self::Random),
-            "entropy" => Some(Self::Entropy),
-            "search" => Some(Self::Search),
-            "unfair" => Some(Self::Unfair),
-            _ => None,
-        }
-    }
-
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Self::Random => "random",
-            Self::Entropy => "entropy",
-            Self::Search => "search",
-            Self::Unfair => "unfair",
-        }
-    }
-}
+pub mod tune;
 
 struct AgentSlot {
-    name: &'static str,
+    name: String,
     agent: Box<dyn Agent<Game = Game>>,
     stats: AgentStats,
 }
@@ -59,12 +29,24 @@ pub struct AgentStats {
 pub struct MatchRecord {
     pub config: &'static str,
     pub seed: usize,
-    pub players: Vec<&'static str>,
+    pub players: Vec<String>,
     pub winner: Vec<usize>,
     pub turns: usize,
     pub elapsed_nanos: u128,
     pub agents: Vec<AgentStats>,
     pub history: Vec<MoveAns>,
+    // 反則で打ち切られたときの反則したプレイヤー
+    pub forfeited: Option<usize>,
+    pub faults: Vec<FaultRecord>,
+    // MatchOptions::explain のときだけ、説明できる AI の手について入る
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rationales: Vec<TurnRationale<Move>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MatchOptions {
+    pub runner: RunnerConfig,
+    pub explain: bool,
 }
 
 pub fn stats_config_name() -> &'static str {
@@ -75,59 +57,140 @@ pub fn stats_config() -> GameConfig {
     three_midium()
 }
 
-pub fn run_match(config: &GameConfig, seed: usize, strategies: &[Strategy]) -> MatchRecord {
+pub fn run_match(
+    config: &GameConfig,
+    seed: usize,
+    strategies: &[AgentSpec],
+    options: &MatchOptions,
+) -> MatchRecord {
+    let mut kept = (0..strategies.len()).map(|_| None).collect::<Vec<_>>();
+    play_match(config, seed, strategies, &mut kept, options)
+}
+
+// run_match と同じだが、learns_across_games な strategy の AI は kept に残し、次の試合でもそれを使う。
+// kept は strategies と同じ長さで、最初は全部 None にしておく。
+pub fn play_match(
+    config: &GameConfig,
+    seed: usize,
+    strategies: &[AgentSpec],
+    kept: &mut [Option<Box<dyn Agent<Game = Game>>>],
+    options: &MatchOptions,
+) -> MatchRecord {
+    assert_eq!(kept.len(), strategies.len());
     let mut game = config.gen_random(seed);
+    let mut runner = Runner::new(RunnerConfig {
+        seed: seed as u64,
+        ..options.runner.clone()
+    });
     let mut slots: Vec<_> = strategies
         .iter()
         .enumerate()
         .map(|(player, strategy)| AgentSlot {
-            name: strategy.as_str(),
-            agent: build_agent(*strategy, seed, player),
+            name: strategy.to_string(),
+            agent: kept[player]
+                .take()
+                .unwrap_or_else(|| build_agent_with_oracle(strategy, seed, player, &game.distr())),
             stats: AgentStats::default(),
         })
         .collect();
+    for (seat, slot) in slots.iter_mut().enumerate() {
+        slot.agent.on_game_start(config, seat);
+    }
 
     let started = Instant::now();
     let mut turns = 0;
+    let mut forfeited = None;
+    let mut rationales = vec![];
     while game.is_win().is_none() {
         let player = game.player_turn();
         let (info, possible_moves) = game.info_and_move_now();
         let slot = &mut slots[player];
         let think_started = Instant::now();
-        let action = slot.agent.use_info(info, possible_moves);
+        let faults = runner.faults().len();
+        let action = runner.choose(&mut slot.agent, player, turns, info, possible_moves);
         let think_elapsed = think_started.elapsed().as_nanos();
+        let Some(action) = action else {
+            forfeited = Some(player);
+            break;
+        };
+        // 反則して代わりの手になったなら、agent の rationale はその手の理由ではない
+        if options.explain && runner.faults().len() == faults {
+            if let Some(rationale) = slot.agent.rationale() {
+                rationales.push(TurnRationale {
+                    turn: turns,
+                    player,
+                    rationale,
+                });
+            }
+        }
         slot.stats.move_count += 1;
         slot.stats.think_nanos += think_elapsed;
         match &action {
             Move::Query { .. } => slot.stats.query_count += 1,
             Move::Declare { .. } => slot.stats.declare_count += 1,
         }
-        if !game.move_game(action) {
-            panic!("agent produced illegal move");
+        game.move_game(action);
+        let last = game.history().pop().unwrap();
+        for slot in slots.iter_mut() {
+            slot.agent.observe(&last);
         }
         turns += 1;
     }
+    let winner = game
+        .is_win()
+        .unwrap_or_else(|| vec![0; config.player_num()]);
+    for slot in slots.iter_mut() {
+        slot.agent.on_game_end(&winner);
+    }
+
+    let elapsed_nanos = started.elapsed().as_nanos();
+    let mut players = vec![];
+    let mut agents = vec![];
+    for (player, slot) in slots.into_iter().enumerate() {
+        // 時間切れで手放した AI の代わりは残さず、次の試合で作り直す
+        if strategies[player].learns_across_games() && !runner.is_abandoned(player) {
+            kept[player] = Some(slot.agent);
+        }
+        players.push(slot.name);
+        agents.push(slot.stats);
+    }
 
     MatchRecord {
         config: stats_config_name(),
         seed,
-        players: slots.iter().map(|slot| slot.name).collect(),
-        winner: game.is_win().unwrap(),
+        players,
+        winner,
         turns,
-        elapsed_nanos: started.elapsed().as_nanos(),
-        agents: slots.into_iter().map(|slot| slot.stats).collect(),
+        elapsed_nanos,
+        agents,
         history: game.history(),
+        forfeited,
+        faults: runner.into_faults(),
+        rationales,
     }
 }
 
-fn build_agent(strategy: Strategy, seed: usize, player: usize) -> Box<dyn Agent<Game = Game>> {
-    match strategy {
-        Strategy::Random => Box::new(RandomPlayer::new(SmallRng::seed_from_u64(
-            seed as u64 + player as u64 + 1,
-        ))),
-        Strategy::Entropy => Box::new(UseEntropyPlayer),
-        Strategy::Search => Box::new(SearchPlayer::new(2)),
-        Strategy::Unfair => Box::new(Unfair::new(0.7)),
+// AI の作り方は game-registry にある。ここでは席ごとに seed を変えるだけ
+pub fn build_agent(spec: &AgentSpec, seed: usize, player: usize) -> Box<dyn Agent<Game = Game>> {
+    spec.build(&build_context(seed, player, None))
+        .unwrap_or_else(|err| panic!("{err}; use build_agent_with_oracle"))
+}
+
+// build_agent と同じだが、cheater には本当の配置 distr を渡す
+pub fn build_agent_with_oracle(
+    spec: &AgentSpec,
+    seed: usize,
+    player: usize,
+    distr: &Distr,
+) -> Box<dyn Agent<Game = Game>> {
+    spec.build(&build_context(seed, player, Some(distr.clone())))
+        .unwrap()
+}
+
+fn build_context(seed: usize, player: usize, oracle: Option<Distr>) -> BuildContext {
+    BuildContext {
+        seed: Some(seed as u64 + player as u64 + 1),
+        oracle,
     }
 }
 
@@ -141,7 +204,11 @@ mod tests {
         let record = run_match(
             &config,
             42,
-            &[Strategy::Random, Strategy::Entropy, Strategy::Search],
+            &["random", "entropy", "search"].map(|name| AgentSpec::parse(name).unwrap()),
+            &MatchOptions {
+                explain: true,
+                ..Default::default()
+            },
         );
         assert_eq!(record.config, "three_midium");
         assert_eq!(record.players.len(), 3);
@@ -149,5 +216,9 @@ mod tests {
         assert_eq!(record.winner.len(), 3);
         assert!(record.turns > 0);
         assert_eq!(record.history.len(), record.turns);
+        assert!(record.faults.is_empty());
+        // random 以外は説明を返す
+        assert!(record.rationales.iter().all(|r| r.player != 0));
+        assert!(!record.rationales.is_empty());
     }
 }
@@ -1,9 +1,23 @@
-use std::io::{self, BufWriter, Write};
+use std::{
+    io::{self, BufWriter, Write},
+    time::Duration,
+};
 
-use game_stats::{run_match, stats_config, Strategy};
+use game_core::runner::{FaultPolicy, RunnerConfig};
+use game_registry::AgentSpec;
+use game_stats::{
+    play_match, stats_config,
+    tune::{tune, TuneOptions, TuneSpec},
+    MatchOptions,
+};
 
 fn main() {
-    let args = match parse_args(std::env::args().skip(1).collect()) {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("tune") {
+        tune_main(args[1..].to_vec());
+        return;
+    }
+    let args = match parse_args(args) {
         Ok(args) => args,
         Err(message) => usage_and_exit(&message),
     };
@@ -12,19 +26,24 @@ fn main() {
     let stdout = io::stdout();
     let mut records_writer = BufWriter::new(stdout.lock());
 
+    // meta:bandit などは試合をまたいで同じ AI を使う
+    let mut kept = (0..args.strategies.len()).map(|_| None).collect::<Vec<_>>();
     for seed in 0..args.games {
-        let record = run_match(&config, seed, &args.strategies);
+        let record = play_match(&config, seed, &args.strategies, &mut kept, &args.options);
         serde_json::to_writer(&mut records_writer, &record).expect("failed to serialize record");
         records_writer
             .write_all(b"\n")
             .expect("failed to write newline");
     }
-    records_writer.flush().expect("failed to flush records output");
+    records_writer
+        .flush()
+        .expect("failed to flush records output");
 }
 
 struct Args {
     games: usize,
-    strategies: Vec<Strategy>,
+    strategies: Vec<AgentSpec>,
+    options: MatchOptions,
 }
 
 fn parse_args(args: Vec<String>) -> Result<Args, String> {
@@ -37,10 +56,14 @@ fn parse_args(args: Vec<String>) -> Result<Args, String> {
 
     let strategies = args[..player_num]
         .iter()
-        .map(|name| Strategy::parse(name).ok_or_else(|| format!("unknown strategy: {name}")))
+        .map(|spec| AgentSpec::parse(spec))
         .collect::<Result<Vec<_>, _>>()?;
 
     let mut games = 100;
+    let mut on_fault = "random".to_string();
+    let mut retries = 1;
+    let mut time_limit = None;
+    let mut explain = false;
 
     let mut i = player_num;
     while i < args.len() {
@@ -49,14 +72,139 @@ fn parse_args(args: Vec<String>) -> Result<Args, String> {
                 i += 1;
                 games = parse_usize(args.get(i), "--games")?;
             }
+            "--on-fault" => {
+                i += 1;
+                on_fault = args.get(i).cloned().ok_or("missing value for --on-fault")?;
+            }
+            "--retries" => {
+                i += 1;
+                retries = parse_usize(args.get(i), "--retries")?;
+            }
+            "--time-limit-ms" => {
+                i += 1;
+                let ms = parse_usize(args.get(i), "--time-limit-ms")?;
+                time_limit = Some(Duration::from_millis(ms as u64));
+            }
+            "--explain" => explain = true,
             flag => return Err(format!("unknown argument: {flag}")),
         }
         i += 1;
     }
 
+    let policy = match on_fault.as_str() {
+        "forfeit" => FaultPolicy::Forfeit,
+        "random" => FaultPolicy::Random,
+        "retry" => FaultPolicy::Retry { retries },
+        other => return Err(format!("unknown fault policy: {other}")),
+    };
+
     Ok(Args {
         games,
         strategies,
+        options: MatchOptions {
+            runner: RunnerConfig {
+                time_limit,
+                policy,
+                ..Default::default()
+            },
+            explain,
+        },
+    })
+}
+
+// params の候補を並べて対戦させ、勝率の順に表を出す
+fn tune_main(args: Vec<String>) {
+    let args = match parse_tune_args(args) {
+        Ok(args) => args,
+        Err(message) => usage_and_exit(&message),
+    };
+    let candidates = match args.random {
+        Some(samples) => args.spec.random(samples, args.options.seed as u64),
+        None => args.spec.grid(args.steps),
+    }
+    .unwrap_or_else(|err| usage_and_exit(&err));
+    eprintln!("{} candidates", candidates.len());
+
+    let results = tune(
+        &stats_config(),
+        candidates,
+        &args.options,
+        |round, alive| {
+            eprintln!("round {round}: {alive} candidates played");
+        },
+    );
+    println!("# rank spec games wins win_rate ci_low ci_high rounds");
+    for (rank, result) in results.iter().enumerate() {
+        let (low, high) = result.confidence_interval();
+        println!(
+            "{} {} {} {} {:.3} {low:.3} {high:.3} {}",
+            rank + 1,
+            result.spec,
+            result.games,
+            result.wins,
+            result.win_rate(),
+            result.rounds
+        );
+    }
+}
+
+struct TuneArgs {
+    spec: TuneSpec,
+    steps: usize,
+    random: Option<usize>,
+    options: TuneOptions,
+}
+
+fn parse_tune_args(args: Vec<String>) -> Result<TuneArgs, String> {
+    let spec = TuneSpec::parse(args.first().ok_or("missing spec to tune")?)?;
+    let mut steps = 3;
+    let mut random = None;
+    let mut options = TuneOptions {
+        games: 50,
+        halving: false,
+        seed: 0,
+        opponents: vec![],
+        match_options: MatchOptions::default(),
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--games" => {
+                i += 1;
+                options.games = parse_usize(args.get(i), "--games")?.max(1);
+            }
+            "--opponent" => {
+                i += 1;
+                let spec = args.get(i).ok_or("missing value for --opponent")?;
+                options.opponents.push(AgentSpec::parse(spec)?);
+            }
+            "--grid" => {
+                i += 1;
+                steps = parse_usize(args.get(i), "--grid")?;
+            }
+            "--random" => {
+                i += 1;
+                random = Some(parse_usize(args.get(i), "--random")?);
+            }
+            "--halving" => options.halving = true,
+            "--seed" => {
+                i += 1;
+                options.seed = parse_usize(args.get(i), "--seed")?;
+            }
+            flag => return Err(format!("unknown argument: {flag}")),
+        }
+        i += 1;
+    }
+    if options.opponents.is_empty() {
+        options.opponents = vec![AgentSpec::parse("entropy")?, AgentSpec::parse("unfair")?];
+    }
+
+    Ok(TuneArgs {
+        spec,
+        steps,
+        random,
+        options,
     })
 }
 
@@ -69,7 +217,10 @@ fn parse_usize(value: Option<&String>, flag: &str) -> Result<usize, String> {
 
 fn usage_and_exit(message: &str) -> ! {
     eprintln!("{message}");
-    eprintln!("usage: game-stats <p0> <p1> <p2> [--games N]");
+    eprintln!("usage: game-stats <p0> <p1> <p2> [--games N] [--on-fault forfeit|random|retry] [--retries N] [--time-limit-ms N] [--explain]");
+    eprintln!("       game-stats tune <spec with ranges> [--opponent SPEC]... [--games N] [--grid STEPS | --random N] [--halving] [--seed N]");
     eprintln!("example: cargo run -p game-stats -- random entropy unfair --games 200");
+    eprintln!("example: cargo run --release -p game-stats -- tune unfair:risk=-0.5..0.5 --grid 5 --games 100 --halving");
+    eprintln!("strategies:\n{}", game_registry::help());
     std::process::exit(2);
 }
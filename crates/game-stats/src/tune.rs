@@ -0,0 +1,366 @@
+use game_core::defs::GameConfig;
+use game_registry::{entry, AgentSpec, ParamKind};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{run_match, MatchOptions};
+
+// AI の params の候補を同じ条件で対戦させて並べる。
+// 候補は "unfair:risk=-0.5..0.5" や "search:depth=1..3,samples=0|4" のように、値の代わりに範囲を書いた spec から作る。
+// - "lo..hi" : 範囲。grid なら steps 個に等分し、random なら一様に選ぶ（整数の param なら整数）
+// - "a|b|c"  : 並べた値
+// 試合 i は seed + i の配り方で、候補は席 i % player_num に座り、ほかの席には相手を順に座らせる。
+// なのでどの候補も同じ配り方・同じ席・同じ相手で比べられる。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamRange {
+    Values(Vec<String>),
+    Int { min: i64, max: i64 },
+    Float { min: f64, max: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneSpec {
+    name: String,
+    params: Vec<(String, ParamRange)>,
+}
+
+impl TuneSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, rest) = match spec.split_once(':') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (spec, None),
+        };
+        if name == "meta" {
+            return Err("meta can not be tuned".to_string());
+        }
+        let entry = entry(name).ok_or_else(|| format!("unknown strategy: {name}"))?;
+        let mut params = vec![];
+        for param in rest.into_iter().flat_map(|rest| rest.split(',')) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value: {param}"))?;
+            let kind = entry
+                .params
+                .iter()
+                .find(|param| param.name == key)
+                .ok_or_else(|| format!("{name} has no param {key}"))?
+                .kind;
+            params.push((key.to_string(), parse_range(value, kind)?));
+        }
+        let parsed = Self {
+            name: name.to_string(),
+            params,
+        };
+        // 名前と params の名前をここで確かめておく
+        parsed.spec(
+            &parsed
+                .params
+                .iter()
+                .map(|(_, range)| range.first())
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(parsed)
+    }
+
+    // 全部の組み合わせ。範囲は steps 個に等分する
+    pub fn grid(&self, steps: usize) -> Result<Vec<AgentSpec>, String> {
+        let mut combinations: Vec<Vec<String>> = vec![vec![]];
+        for (_, range) in &self.params {
+            let values = range.grid(steps);
+            combinations = combinations
+                .into_iter()
+                .flat_map(|chosen| {
+                    values.iter().map(move |value| {
+                        let mut chosen = chosen.clone();
+                        chosen.push(value.clone());
+                        chosen
+                    })
+                })
+                .collect();
+        }
+        combinations
+            .iter()
+            .map(|values| self.spec(values))
+            .collect()
+    }
+
+    // samples 個を選ぶ。同じものが出たら 1 つにまとめる
+    pub fn random(&self, samples: usize, seed: u64) -> Result<Vec<AgentSpec>, String> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut specs: Vec<AgentSpec> = vec![];
+        for _ in 0..samples {
+            let values: Vec<String> = self
+                .params
+                .iter()
+                .map(|(_, range)| range.sample(&mut rng))
+                .collect();
+            let spec = self.spec(&values)?;
+            if !specs.contains(&spec) {
+                specs.push(spec);
+            }
+        }
+        Ok(specs)
+    }
+
+    fn spec(&self, values: &[String]) -> Result<AgentSpec, String> {
+        if self.params.is_empty() {
+            return AgentSpec::parse(&self.name);
+        }
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .zip(values)
+            .map(|((key, _), value)| format!("{key}={value}"))
+            .collect();
+        AgentSpec::parse(&format!("{}:{}", self.name, params.join(",")))
+    }
+}
+
+// 範囲を整数で刻むか実数で刻むかは、両端の書き方ではなく param の種類で決める
+fn parse_range(value: &str, kind: ParamKind) -> Result<ParamRange, String> {
+    if let Some((min, max)) = value.split_once("..") {
+        let range = match kind {
+            ParamKind::Int { .. } => {
+                let (Ok(min), Ok(max)) = (min.parse::<i64>(), max.parse::<i64>()) else {
+                    return Err(format!("expected integers: {value}"));
+                };
+                (min <= max).then_some(ParamRange::Int { min, max })
+            }
+            ParamKind::Float { .. } => {
+                let (Ok(min), Ok(max)) = (min.parse::<f64>(), max.parse::<f64>()) else {
+                    return Err(format!("expected numbers: {value}"));
+                };
+                (min <= max).then_some(ParamRange::Float { min, max })
+            }
+            ParamKind::Text => return Err(format!("text can not be a range: {value}")),
+        };
+        return range.ok_or_else(|| format!("empty range: {value}"));
+    }
+    Ok(ParamRange::Values(
+        value.split('|').map(str::to_string).collect(),
+    ))
+}
+
+impl ParamRange {
+    fn first(&self) -> String {
+        match self {
+            Self::Values(values) => values[0].clone(),
+            Self::Int { min, .. } => min.to_string(),
+            Self::Float { min, .. } => format_float(*min),
+        }
+    }
+
+    fn grid(&self, steps: usize) -> Vec<String> {
+        let steps = steps.max(1);
+        let at = |k: usize| {
+            if steps == 1 {
+                0.5
+            } else {
+                k as f64 / (steps - 1) as f64
+            }
+        };
+        match self {
+            Self::Values(values) => values.clone(),
+            Self::Int { min, max } => {
+                let mut values: Vec<i64> = (0..steps)
+                    .map(|k| min + ((max - min) as f64 * at(k)).round() as i64)
+                    .collect();
+                values.dedup();
+                values.iter().map(i64::to_string).collect()
+            }
+            Self::Float { min, max } => (0..steps)
+                .map(|k| format_float(min + (max - min) * at(k)))
+                .collect(),
+        }
+    }
+
+    fn sample(&self, rng: &mut SmallRng) -> String {
+        match self {
+            Self::Values(values) => values[rng.gen_range(0..values.len())].clone(),
+            Self::Int { min, max } => rng.gen_range(*min..=*max).to_string(),
+            Self::Float { min, max } => format_float(rng.gen_range(*min..=*max)),
+        }
+    }
+}
+
+// spec に書くので、桁を丸めて末尾の 0 を落とす
+fn format_float(value: f64) -> String {
+    let formatted = format!("{value:.4}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    match formatted {
+        "-0" => "0".to_string(),
+        formatted => formatted.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneOptions {
+    // 候補ごとの試合数。halving なら 1 回目の数で、残った候補には合計が倍になるまで試合を足していく
+    pub games: usize,
+    // 上位半分だけを残して試合を足すのを、1 つになるまで繰り返す
+    pub halving: bool,
+    // 最初の試合の seed
+    pub seed: usize,
+    pub opponents: Vec<AgentSpec>,
+    pub match_options: MatchOptions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneResult {
+    pub spec: AgentSpec,
+    pub games: usize,
+    pub wins: usize,
+    // 何回目の halving まで残ったか（halving しないなら 1）
+    pub rounds: usize,
+}
+
+impl TuneResult {
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            return 0_f64;
+        }
+        self.wins as f64 / self.games as f64
+    }
+
+    // 勝率の 95% 信頼区間（Wilson）
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        wilson_interval(self.wins, self.games, 1.96)
+    }
+}
+
+pub fn wilson_interval(wins: usize, games: usize, z: f64) -> (f64, f64) {
+    if games == 0 {
+        return (0_f64, 1_f64);
+    }
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let denominator = 1_f64 + z * z / n;
+    let center = (p + z * z / (2_f64 * n)) / denominator;
+    let half = z * (p * (1_f64 - p) / n + z * z / (4_f64 * n * n)).sqrt() / denominator;
+    ((center - half).max(0_f64), (center + half).min(1_f64))
+}
+
+// 候補を対戦させ、よい順に並べて返す。progress には各回の (回数, 残った候補の数) を渡す
+pub fn tune(
+    config: &GameConfig,
+    candidates: Vec<AgentSpec>,
+    options: &TuneOptions,
+    mut progress: impl FnMut(usize, usize),
+) -> Vec<TuneResult> {
+    assert!(!options.opponents.is_empty(), "相手がいない");
+    let mut results: Vec<TuneResult> = candidates
+        .into_iter()
+        .map(|spec| TuneResult {
+            spec,
+            games: 0,
+            wins: 0,
+            rounds: 0,
+        })
+        .collect();
+    let mut alive = results.len();
+    let mut target = options.games;
+    loop {
+        for result in &mut results[..alive] {
+            result.wins += play_candidate(config, &result.spec, result.games..target, options);
+            result.games = target;
+            result.rounds += 1;
+        }
+        results[..alive].sort_by(|r1, r2| r2.win_rate().partial_cmp(&r1.win_rate()).unwrap());
+        progress(results[0].rounds, alive);
+        if !options.halving || alive <= 1 {
+            break;
+        }
+        alive = alive.div_ceil(2);
+        target *= 2;
+    }
+    results.sort_by(|r1, r2| {
+        r2.rounds
+            .cmp(&r1.rounds)
+            .then(r2.win_rate().partial_cmp(&r1.win_rate()).unwrap())
+    });
+    results
+}
+
+// games の範囲の試合をして、勝った数を返す
+fn play_candidate(
+    config: &GameConfig,
+    candidate: &AgentSpec,
+    games: std::ops::Range<usize>,
+    options: &TuneOptions,
+) -> usize {
+    let player_num = config.player_num();
+    let mut wins = 0;
+    for game in games {
+        let seat = game % player_num;
+        let mut opponents = (0..).map(|k| &options.opponents[(game + k) % options.opponents.len()]);
+        let strategies: Vec<AgentSpec> = (0..player_num)
+            .map(|player| {
+                if player == seat {
+                    candidate.clone()
+                } else {
+                    opponents.next().unwrap().clone()
+                }
+            })
+            .collect();
+        let record = run_match(
+            config,
+            options.seed + game,
+            &strategies,
+            &options.match_options,
+        );
+        wins += record.winner[seat];
+    }
+    wins
+}
+
+#[cfg(test)]
+mod tests {
+    use game_core::config::three_small;
+
+    use super::*;
+
+    #[test]
+    fn tune_ranks_expanded_candidates() {
+        let spec = TuneSpec::parse("unfair:risk=-0.5..0.5").unwrap();
+        let grid: Vec<String> = spec
+            .grid(3)
+            .unwrap()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            grid,
+            ["unfair:risk=-0.5", "unfair:risk=0", "unfair:risk=0.5"]
+        );
+        let spec = TuneSpec::parse("search:depth=1..3,samples=0|4").unwrap();
+        assert_eq!(spec.grid(5).unwrap().len(), 6);
+        assert!(spec.random(10, 0).unwrap().len() <= 10);
+        assert!(TuneSpec::parse("search:depth=0..3").is_err());
+        assert!(TuneSpec::parse("search:width=1..3").is_err());
+        // 実数の param なら、両端が整数でも実数で刻む
+        let spec = TuneSpec::parse("unfair:risk=-1..1").unwrap();
+        assert_eq!(spec.grid(5).unwrap().len(), 5);
+
+        let candidates = TuneSpec::parse("random:seed=1|2|3|4")
+            .unwrap()
+            .grid(1)
+            .unwrap();
+        let options = TuneOptions {
+            games: 4,
+            halving: true,
+            seed: 0,
+            opponents: vec![AgentSpec::parse("entropy").unwrap()],
+            match_options: MatchOptions::default(),
+        };
+        let mut rounds = vec![];
+        let results = tune(&three_small(), candidates, &options, |round, alive| {
+            rounds.push((round, alive))
+        });
+        assert_eq!(rounds, [(1, 4), (2, 2), (3, 1)]);
+        assert_eq!(results.len(), 4);
+        assert_eq!((results[0].games, results[0].rounds), (16, 3));
+        assert_eq!((results[3].games, results[3].rounds), (4, 1));
+        let (low, high) = results[0].confidence_interval();
+        assert!(low <= results[0].win_rate() && results[0].win_rate() <= high);
+    }
+}
@@ -0,0 +1,211 @@
+use game_ai_learned::{features, Checkpoint, Features, LearnedPlayer, MOVE_FEATURE_NUM};
+use game_core::{
+    abstract_game::{Agent, ImperfectInfoGame},
+    defs::Game,
+};
+use game_registry::{AgentSpec, BuildContext};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+// 学習中のモデルを 1 席に座らせ、残りの席に相手を座らせて対戦し、勝敗からモデルを更新する。
+// - 方策は REINFORCE で、価値（勝つ確率の予測）を baseline にする
+// - 価値は勝敗へのロジスティック回帰
+// CPU だけで回せるように、モデルは game-ai/learned の線形モデルにしている。
+
+// 相手の候補
+#[derive(Debug, Clone, PartialEq)]
+pub enum Opponent {
+    // 今の学習中のモデル（確率が一番高い手を打つ）
+    Current,
+    Spec(AgentSpec),
+}
+
+impl Opponent {
+    // "self" なら学習中のモデル、それ以外は game-registry の spec
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "self" => Ok(Self::Current),
+            spec => Ok(Self::Spec(AgentSpec::parse(spec)?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameReport {
+    pub seat: usize,
+    // 決着がつかなければ None
+    pub winner: Option<Vec<usize>>,
+    pub turns: usize,
+}
+
+pub struct Trainer {
+    checkpoint: Checkpoint,
+    opponents: Vec<Opponent>,
+    learning_rate: f64,
+    // この手数で決着がつかなければ全員負けとして扱う
+    max_turns: usize,
+    rng: SmallRng,
+}
+
+// 学習中のモデルが打った 1 手
+struct Step {
+    features: Features,
+    probs: Vec<f64>,
+    chosen: usize,
+}
+
+impl Trainer {
+    pub const DEFAULT_MAX_TURNS: usize = 100;
+
+    pub fn new(
+        checkpoint: Checkpoint,
+        opponents: Vec<Opponent>,
+        learning_rate: f64,
+        seed: u64,
+    ) -> Self {
+        assert!(!opponents.is_empty(), "相手がいない");
+        Self {
+            checkpoint,
+            opponents,
+            learning_rate,
+            max_turns: Self::DEFAULT_MAX_TURNS,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    pub fn into_checkpoint(self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    // 1 ゲーム対戦してモデルを更新する。座る席はゲームごとに回す。
+    pub fn train_game(&mut self) -> Result<GameReport, String> {
+        let config = self.checkpoint.config.clone();
+        let game_seed = self.rng.gen::<u64>();
+        let mut game = config.gen_random(game_seed as usize);
+        let seat = self.checkpoint.games % config.player_num();
+
+        let mut agents: Vec<Option<Box<dyn Agent<Game = Game>>>> = vec![];
+        for player in 0..config.player_num() {
+            if player == seat {
+                agents.push(None);
+                continue;
+            }
+            let opponent = &self.opponents[self.rng.gen_range(0..self.opponents.len())];
+            let agent: Box<dyn Agent<Game = Game>> = match opponent {
+                Opponent::Current => Box::new(LearnedPlayer::new(self.checkpoint.model.clone())),
+                Opponent::Spec(spec) => spec.build(&BuildContext {
+                    seed: Some(game_seed + player as u64),
+                    oracle: Some(game.distr()),
+                })?,
+            };
+            agents.push(Some(agent));
+        }
+        for (player, agent) in agents.iter_mut().enumerate() {
+            if let Some(agent) = agent {
+                agent.on_game_start(&config, player);
+            }
+        }
+
+        let mut steps = vec![];
+        let mut turns = 0;
+        while game.is_win().is_none() && turns < self.max_turns {
+            let player = game.player_turn();
+            let (info, possible_moves) = game.info_and_move_now();
+            let m = match &mut agents[player] {
+                Some(agent) => agent.use_info(info, possible_moves),
+                None => {
+                    let features = features(&info, &possible_moves);
+                    let probs = self.checkpoint.model.policy(&features);
+                    let chosen = self.sample(&probs);
+                    steps.push(Step {
+                        features,
+                        probs,
+                        chosen,
+                    });
+                    possible_moves[chosen].clone()
+                }
+            };
+            game.move_game(m);
+            let last = game.history().pop().unwrap();
+            for agent in agents.iter_mut().flatten() {
+                agent.observe(&last);
+            }
+            turns += 1;
+        }
+        let winner = game.is_win();
+        let outcome = winner
+            .clone()
+            .unwrap_or_else(|| vec![0; config.player_num()]);
+        for agent in agents.iter_mut().flatten() {
+            agent.on_game_end(&outcome);
+        }
+
+        self.update(&steps, outcome[seat] as f64);
+        self.checkpoint.games += 1;
+        Ok(GameReport {
+            seat,
+            winner,
+            turns,
+        })
+    }
+
+    fn sample(&mut self, probs: &[f64]) -> usize {
+        let mut x: f64 = self.rng.gen();
+        for (i, p) in probs.iter().enumerate() {
+            if x < *p {
+                return i;
+            }
+            x -= p;
+        }
+        probs.len() - 1
+    }
+
+    fn update(&mut self, steps: &[Step], reward: f64) {
+        let model = &mut self.checkpoint.model;
+        for step in steps {
+            let value = model.value(&step.features.state);
+            let advantage = reward - value;
+            // log π(chosen) の勾配は 選んだ手の特徴量 - 特徴量の期待値
+            let mut gradient = step.features.moves[step.chosen].clone();
+            for (feature, p) in step.features.moves.iter().zip(&step.probs) {
+                for k in 0..MOVE_FEATURE_NUM {
+                    gradient[k] -= p * feature[k];
+                }
+            }
+            for (w, g) in model.policy.iter_mut().zip(gradient) {
+                *w += self.learning_rate * advantage * g;
+            }
+            for (v, s) in model.value.iter_mut().zip(&step.features.state) {
+                *v += self.learning_rate * advantage * s;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_ai_learned::LinearModel;
+    use game_core::config::three_small;
+
+    use super::*;
+
+    #[test]
+    fn training_updates_the_checkpoint() {
+        let opponents = ["entropy", "random", "self"]
+            .into_iter()
+            .map(|spec| Opponent::parse(spec).unwrap())
+            .collect();
+        let mut trainer = Trainer::new(Checkpoint::new(three_small()), opponents, 0.1, 0);
+        for _ in 0..20 {
+            let report = trainer.train_game().unwrap();
+            assert!(report.turns > 0);
+        }
+        let checkpoint = trainer.into_checkpoint();
+        assert_eq!(checkpoint.games, 20);
+        assert_ne!(checkpoint.model, LinearModel::default());
+        assert!(Opponent::parse("unknown").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use game_ai_learned::Checkpoint;
+use game_core::config;
+use game_train::{Opponent, Trainer};
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(message) => usage_and_exit(&message),
+    };
+
+    let config = config::by_name(&args.config)
+        .unwrap_or_else(|| usage_and_exit(&format!("unknown config: {}", args.config)));
+    let checkpoint = match &args.init {
+        Some(path) => Checkpoint::load(path).expect("failed to load checkpoint"),
+        None => Checkpoint::new(config.clone()),
+    };
+    if checkpoint.config != config {
+        usage_and_exit("checkpoint was trained on a different config");
+    }
+    let mut trainer = Trainer::new(checkpoint, args.opponents, args.learning_rate, args.seed);
+
+    let mut wins = 0;
+    let mut unresolved = 0;
+    // 前に報告したときまでの試合数。最後の区切りは report より短いことがある
+    let mut reported = 0;
+    for done in 1..=args.games {
+        let report = trainer
+            .train_game()
+            .unwrap_or_else(|err| usage_and_exit(&err));
+        match report.winner {
+            Some(winner) => wins += winner[report.seat],
+            None => unresolved += 1,
+        }
+        if done % args.report == 0 || done == args.games {
+            eprintln!(
+                "games: {} win_rate: {:.3} unresolved: {unresolved}",
+                trainer.checkpoint().games,
+                wins as f64 / (done - reported) as f64
+            );
+            wins = 0;
+            unresolved = 0;
+            reported = done;
+        }
+        if done % args.checkpoint_every == 0 || done == args.games {
+            trainer
+                .checkpoint()
+                .save(&args.out)
+                .expect("failed to save checkpoint");
+        }
+    }
+    eprintln!("saved checkpoint to {}", args.out);
+}
+
+struct Args {
+    config: String,
+    games: usize,
+    opponents: Vec<Opponent>,
+    learning_rate: f64,
+    seed: u64,
+    init: Option<String>,
+    report: usize,
+    checkpoint_every: usize,
+    out: String,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        config: "three_midium".to_string(),
+        games: 1000,
+        opponents: vec![],
+        learning_rate: 0.05,
+        seed: 0,
+        init: None,
+        report: 100,
+        checkpoint_every: 100,
+        out: "learned.json".to_string(),
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--config" => {
+                i += 1;
+                parsed.config = parse_string(args.get(i), "--config")?;
+            }
+            "--games" => {
+                i += 1;
+                parsed.games = parse_usize(args.get(i), "--games")?;
+            }
+            "--opponent" => {
+                i += 1;
+                let spec = parse_string(args.get(i), "--opponent")?;
+                parsed.opponents.push(Opponent::parse(&spec)?);
+            }
+            "--lr" => {
+                i += 1;
+                parsed.learning_rate = parse_string(args.get(i), "--lr")?
+                    .parse()
+                    .map_err(|_| "invalid number for --lr")?;
+            }
+            "--seed" => {
+                i += 1;
+                parsed.seed = parse_usize(args.get(i), "--seed")? as u64;
+            }
+            "--init" => {
+                i += 1;
+                parsed.init = Some(parse_string(args.get(i), "--init")?);
+            }
+            "--report" => {
+                i += 1;
+                parsed.report = parse_usize(args.get(i), "--report")?.max(1);
+            }
+            "--checkpoint-every" => {
+                i += 1;
+                parsed.checkpoint_every = parse_usize(args.get(i), "--checkpoint-every")?.max(1);
+            }
+            "--out" => {
+                i += 1;
+                parsed.out = parse_string(args.get(i), "--out")?;
+            }
+            flag => return Err(format!("unknown argument: {flag}")),
+        }
+        i += 1;
+    }
+    if parsed.opponents.is_empty() {
+        parsed.opponents = vec![
+            Opponent::parse("entropy")?,
+            Opponent::parse("unfair")?,
+            Opponent::Current,
+        ];
+    }
+
+    Ok(parsed)
+}
+
+fn parse_string(value: Option<&String>, flag: &str) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("missing value for {flag}"))
+}
+
+fn parse_usize(value: Option<&String>, flag: &str) -> Result<usize, String> {
+    value
+        .ok_or_else(|| format!("missing value for {flag}"))?
+        .parse::<usize>()
+        .map_err(|_| format!("invalid integer for {flag}"))
+}
+
+fn usage_and_exit(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("usage: game-train [--config NAME] [--games N] [--opponent SPEC]... [--lr X] [--seed N] [--init PATH] [--report N] [--checkpoint-every N] [--out PATH]");
+    eprintln!("example: cargo run --release -p game-train -- --games 2000 --opponent entropy --opponent unfair --opponent self --out learned.json");
+    std::process::exit(2);
+}
@@ -22,12 +22,19 @@ const elements = {
   allCards: document.getElementById("all-cards"),
   viewTableBody: document.getElementById("view-table-body"),
   historyJson: document.getElementById("history-json"),
+  rationaleBox: document.getElementById("rationale-box"),
   possibleMoves: document.getElementById("possible-moves"),
+  remoteForm: document.getElementById("remote-form"),
+  remoteUrl: document.getElementById("remote-url"),
+  remoteGame: document.getElementById("remote-game"),
+  remoteSecret: document.getElementById("remote-secret"),
 };
 
 let wasmReady = false;
 let game = null;
 let currentState = null;
+// server の /ws につないで遊んでいるときの WebSocket。つないでいる間は game より優先する
+let remote = null;
 
 boot().catch((error) => {
   setError(`init failed: ${error instanceof Error ? error.message : String(error)}`);
@@ -55,6 +62,7 @@ elements.startForm.addEventListener("submit", (event) => {
     const seed = Number.parseInt(elements.seed.value, 10) || 0;
     const userPlayer = Number.parseInt(elements.userPlayer.value, 10) || 0;
     const ai = [elements.ai1.value, elements.ai2.value];
+    disconnectRemote();
     game = new WebGame(seed, userPlayer, JSON.stringify(ai));
     renderState(JSON.parse(game.state_json()));
     setStatus("ready", "Game started.");
@@ -63,23 +71,37 @@ elements.startForm.addEventListener("submit", (event) => {
 
 elements.refreshState.addEventListener("click", () => {
   withGuard(() => {
+    if (remote) {
+      sendRemote({ type: "state" });
+      return;
+    }
     assertGame();
     renderState(JSON.parse(game.state_json()));
     setStatus("ready", "State refreshed.");
   });
 });
 
+elements.remoteForm.addEventListener("submit", (event) => {
+  event.preventDefault();
+  withGuard(() => {
+    connectRemote(
+      elements.remoteUrl.value.trim(),
+      Number.parseInt(elements.remoteGame.value, 10) || 0,
+      elements.remoteSecret.value.trim(),
+    );
+  });
+});
+
 elements.queryForm.addEventListener("submit", (event) => {
   event.preventDefault();
   withGuard(() => {
-    assertGame();
     const move = {
       Query: {
         query_to: Number.parseInt(elements.queryTo.value, 10),
         query_sort: elements.querySort.value.trim(),
       },
     };
-    renderState(JSON.parse(game.play_move_json(JSON.stringify(move))));
+    playMove(move);
     setStatus("ready", "Query submitted.");
   });
 });
@@ -87,18 +109,89 @@ elements.queryForm.addEventListener("submit", (event) => {
 elements.declareForm.addEventListener("submit", (event) => {
   event.preventDefault();
   withGuard(() => {
-    assertGame();
     const declare = elements.declareCards.value
       .trim()
       .split(/\s+/)
       .filter(Boolean)
       .map((value) => Number.parseInt(value, 10));
     const move = { Declare: { declare } };
-    renderState(JSON.parse(game.play_move_json(JSON.stringify(move))));
+    playMove(move);
     setStatus("ready", "Declare submitted.");
   });
 });
 
+function playMove(move) {
+  if (remote) {
+    sendRemote({ type: "move", action: move });
+    return;
+  }
+  assertGame();
+  renderState(JSON.parse(game.play_move_json(JSON.stringify(move))));
+}
+
+// server とは join / state / move を送り、返事と event を受け取る。
+// event が来たら state を聞き直す
+function connectRemote(url, gameId, secret) {
+  disconnectRemote();
+  const socket = new WebSocket(url);
+  remote = socket;
+  setStatus("idle", `Connecting to ${url} ...`);
+
+  socket.addEventListener("open", () => {
+    const join = { type: "join", game: gameId };
+    if (secret) {
+      join.secret = secret;
+    }
+    socket.send(JSON.stringify(join));
+  });
+  socket.addEventListener("message", (event) => {
+    const message = JSON.parse(event.data);
+    switch (message.type) {
+      case "join":
+        elements.remoteSecret.value = message.secret;
+        setStatus("ready", `Joined game ${gameId} as player ${message.player}.`);
+        socket.send(JSON.stringify({ type: "state" }));
+        break;
+      case "state":
+        renderState(message);
+        break;
+      case "move":
+        setStatus(
+          "ready",
+          message.winner ? `Game finished: ${JSON.stringify(message.winner)}` : "Move accepted.",
+        );
+        break;
+      case "event":
+        socket.send(JSON.stringify({ type: "state" }));
+        break;
+      case "error":
+        setError(message.error);
+        break;
+    }
+  });
+  socket.addEventListener("close", () => {
+    if (remote === socket) {
+      remote = null;
+      setStatus("idle", "Disconnected from the server.");
+    }
+  });
+}
+
+function disconnectRemote() {
+  if (remote) {
+    const socket = remote;
+    remote = null;
+    socket.close();
+  }
+}
+
+function sendRemote(message) {
+  if (remote.readyState !== WebSocket.OPEN) {
+    throw new Error("not connected to the server yet");
+  }
+  remote.send(JSON.stringify(message));
+}
+
 function renderState(state) {
   currentState = state;
   renderConfig(state.info.config);
@@ -107,9 +200,13 @@ function renderState(state) {
   appendSummary(`current turn: ${state.current_turn}`);
   appendSummary(`your turn: ${state.your_turn}`);
   appendSummary(`winner: ${JSON.stringify(state.winner)}`);
+  for (const opponent of state.opponents ?? []) {
+    appendSummary(`player ${opponent.player} can declare: ${(opponent.certain * 100).toFixed(0)}%`);
+  }
 
   renderViewTable(state);
   elements.historyJson.textContent = JSON.stringify(state.info.query_answer, null, 2);
+  elements.rationaleBox.textContent = formatRationales(state.rationales ?? []);
 
   elements.possibleMoves.innerHTML = "";
   for (const move of state.possible_moves) {
@@ -143,6 +240,37 @@ function formatMove(move) {
   return JSON.stringify(move);
 }
 
+// 新しい手から順に、判断と値の良い候補を数個ずつ出す
+function formatRationales(rationales) {
+  const lines = [];
+  for (const { turn, player, rationale } of [...rationales].reverse()) {
+    const worlds = rationale.worlds === null ? "" : ` worlds ${rationale.worlds}`;
+    lines.push(`turn ${turn} player ${player}:${worlds}`);
+    if (rationale.decision) {
+      const values = rationale.decision.values
+        .map(([name, value]) => `${name}=${value.toFixed(3)}`)
+        .join(" ");
+      lines.push(`  declare ${rationale.decision.declare} (${values})`);
+    }
+    const byKind = new Map();
+    for (const candidate of rationale.candidates) {
+      if (!byKind.has(candidate.kind)) {
+        byKind.set(candidate.kind, []);
+      }
+      byKind.get(candidate.kind).push(candidate);
+    }
+    for (const [kind, candidates] of byKind) {
+      // entropy は小さいほど良く、それ以外は大きいほど良い
+      const sign = kind === "entropy" ? 1 : -1;
+      candidates.sort((a, b) => sign * (a.value - b.value));
+      for (const candidate of candidates.slice(0, 3)) {
+        lines.push(`  ${kind} ${candidate.value.toFixed(3)} ${formatMove(candidate.action)}`);
+      }
+    }
+  }
+  return lines.join("\n");
+}
+
 function appendSummary(text) {
   const item = document.createElement("span");
   item.textContent = text;
@@ -1,15 +1,12 @@
 #![allow(unexpected_cfgs)]
 
-use game_ai_entropy::UseEntropyPlayer;
-use game_ai_random::RandomPlayer;
-use game_ai_search::SearchPlayer;
-use game_ai_unfair::Unfair;
 use game_core::{
-    abstract_game::{Agent, ImperfectInfoGame},
+    abstract_game::{Agent, ImperfectInfoGame, TurnRationale},
     config::three_midium,
     defs::{Game, Info, Move},
+    utils::opponent::{opponent_knowledge, OpponentKnowledge},
 };
-use rand::{rngs::SmallRng, SeedableRng};
+use game_registry::{AgentSpec, BuildContext};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
@@ -21,10 +18,14 @@ pub struct WebState {
     pub winner: Option<Vec<usize>>,
     pub info: Info,
     pub possible_moves: Vec<Move>,
+    // 各相手がもう宣言できそうか
+    pub opponents: Vec<OpponentKnowledge>,
+    // AI が手を選んだ理由（説明できる AI のみ）。AI の手札から計算した値が入るので、決着するまでは空
+    pub rationales: Vec<TurnRationale<Move>>,
 }
 
 impl WebState {
-    fn from_game(game: &Game, you: usize) -> Self {
+    fn from_game(game: &Game, you: usize, rationales: &[TurnRationale<Move>]) -> Self {
         let winner = game.is_win();
         let current_turn = game.player_turn();
         let (base_info, current_moves) = game.info_and_move_now();
@@ -38,6 +39,16 @@ impl WebState {
         } else {
             vec![]
         };
+        let opponents = if winner.is_none() {
+            opponent_knowledge(&info)
+        } else {
+            vec![]
+        };
+        let rationales = if winner.is_some() {
+            rationales.to_vec()
+        } else {
+            vec![]
+        };
         Self {
             you,
             current_turn,
@@ -45,24 +56,18 @@ impl WebState {
             winner,
             info,
             possible_moves,
+            opponents,
+            rationales,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum WebAi {
-    Random,
-    Entropy,
-    Search,
-    Unfair,
-}
-
 #[wasm_bindgen]
 pub struct WebGame {
     game: Game,
     ai_players: Vec<Option<Box<dyn Agent<Game = Game>>>>,
     user_player: usize,
+    rationales: Vec<TurnRationale<Move>>,
 }
 
 #[wasm_bindgen]
@@ -83,23 +88,30 @@ impl WebGame {
             (0..config.player_num()).map(|_| None).collect();
         for (player, strategy) in (0..config.player_num())
             .filter(|player| *player != user_player)
-            .zip(ai.into_iter())
+            .zip(ai)
         {
-            ai_players[player] = Some(build_ai(strategy, seed, player));
+            let mut agent = build_ai(&strategy, seed, player)?;
+            agent.on_game_start(&config, player);
+            ai_players[player] = Some(agent);
         }
 
         let mut web_game = Self {
             game,
             ai_players,
             user_player,
+            rationales: vec![],
         };
         web_game.run_ai_turns()?;
         Ok(web_game)
     }
 
     pub fn state_json(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&WebState::from_game(&self.game, self.user_player))
-            .map_err(|err| JsValue::from_str(&err.to_string()))
+        serde_json::to_string(&WebState::from_game(
+            &self.game,
+            self.user_player,
+            &self.rationales,
+        ))
+        .map_err(|err| JsValue::from_str(&err.to_string()))
     }
 
     pub fn play_move_json(&mut self, move_json: &str) -> Result<String, JsValue> {
@@ -115,6 +127,7 @@ impl WebGame {
         if !self.game.move_game(action) {
             return Err(JsValue::from_str("illegal move"));
         }
+        self.notify_last_move();
 
         self.run_ai_turns()?;
         self.state_json()
@@ -130,26 +143,55 @@ impl WebGame {
             };
             let (info, possible_moves) = self.game.info_and_move_now();
             let action = agent.use_info(info, possible_moves);
+            if let Some(rationale) = agent.rationale() {
+                self.rationales.push(TurnRationale {
+                    turn: self.game.history().len(),
+                    player,
+                    rationale,
+                });
+            }
             if !self.game.move_game(action) {
                 return Err(JsValue::from_str("ai produced an illegal move"));
             }
+            self.notify_last_move();
         }
         Ok(())
     }
+
+    // 指された手を AI 全員に渡し、決着がついていれば終了も知らせる。
+    fn notify_last_move(&mut self) {
+        let Some(last) = self.game.history().pop() else {
+            return;
+        };
+        let winner = self.game.is_win();
+        for agent in self.ai_players.iter_mut().flatten() {
+            agent.observe(&last);
+            if let Some(winner) = &winner {
+                agent.on_game_end(winner);
+            }
+        }
+    }
 }
 
-fn parse_ai(ai_json: &str) -> Result<Vec<WebAi>, JsValue> {
-    serde_json::from_str(ai_json)
-        .map_err(|err| JsValue::from_str(&format!("invalid ai json: {err}")))
+// ai_json は strategy の spec の配列（"entropy" や "search:depth=3"）
+fn parse_ai(ai_json: &str) -> Result<Vec<AgentSpec>, JsValue> {
+    let specs: Vec<String> = serde_json::from_str(ai_json)
+        .map_err(|err| JsValue::from_str(&format!("invalid ai json: {err}")))?;
+    specs
+        .iter()
+        .map(|spec| AgentSpec::parse(spec).map_err(|err| JsValue::from_str(&err)))
+        .collect()
 }
 
-fn build_ai(strategy: WebAi, seed: usize, player: usize) -> Box<dyn Agent<Game = Game>> {
-    match strategy {
-        WebAi::Random => Box::new(RandomPlayer::new(SmallRng::seed_from_u64(
-            seed as u64 + player as u64 + 1,
-        ))),
-        WebAi::Entropy => Box::new(UseEntropyPlayer),
-        WebAi::Search => Box::new(SearchPlayer::new(2)),
-        WebAi::Unfair => Box::new(Unfair::new(0.7)),
-    }
+// 本当の配置は渡さないので、cheater はここでは作れない
+fn build_ai(
+    spec: &AgentSpec,
+    seed: usize,
+    player: usize,
+) -> Result<Box<dyn Agent<Game = Game>>, JsValue> {
+    spec.build(&BuildContext {
+        seed: Some(seed as u64 + player as u64 + 1),
+        oracle: None,
+    })
+    .map_err(|err| JsValue::from_str(&err))
 }